@@ -0,0 +1,134 @@
+use similar::TextDiff;
+use std::path::Path;
+
+use super::manifest::Manifest;
+use super::output::BuildOutput;
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Created => write!(f, "created"),
+            Change::Modified => write!(f, "modified"),
+            Change::Deleted => write!(f, "deleted"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FileChange {
+    /// Path relative to the build directory, separated by `/`
+    pub path: String,
+    pub change: Change,
+    /// Unified diff of the previous and new contents of modified HTML files, when requested
+    pub diff: Option<String>,
+}
+
+/// Lists the changes writing `output` would make to the build directory, including the stale
+/// files a pruning build would delete.
+pub fn compare(
+    output: &BuildOutput,
+    build_directory: &Path,
+    prune: bool,
+    with_diffs: bool,
+) -> Vec<FileChange> {
+    let mut changes = Vec::new();
+
+    for (path, file) in output.files() {
+        let existing_path = build_directory.join(path);
+        let path = crate::utils::to_url_path(path);
+
+        let existing_contents = match std::fs::read(&existing_path) {
+            Ok(contents) => contents,
+            Err(_) => {
+                changes.push(FileChange {
+                    path,
+                    change: Change::Created,
+                    diff: None,
+                });
+                continue;
+            }
+        };
+
+        let new_contents = file.read();
+        if existing_contents == new_contents {
+            continue;
+        }
+
+        let diff = match with_diffs && path.ends_with(".html") {
+            true => Some(unified_diff(&path, &existing_contents, &new_contents)),
+            false => None,
+        };
+
+        changes.push(FileChange {
+            path,
+            change: Change::Modified,
+            diff,
+        });
+    }
+
+    if prune {
+        let previous_manifest = Manifest::load(build_directory);
+        let current_manifest = Manifest::from_output(output);
+
+        let deleted = previous_manifest
+            .stale_files(&current_manifest)
+            .filter(|path| build_directory.join(path).exists())
+            .map(|path| FileChange {
+                path: path.clone(),
+                change: Change::Deleted,
+                diff: None,
+            });
+
+        changes.extend(deleted);
+    }
+
+    changes
+}
+
+fn unified_diff(path: &str, previous: &[u8], new: &[u8]) -> String {
+    let previous = String::from_utf8_lossy(previous);
+    let new = String::from_utf8_lossy(new);
+
+    TextDiff::from_lines(previous.as_ref(), new.as_ref())
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+#[cfg(test)]
+mod test_changes {
+    use super::{compare, Change};
+    use crate::build::output::BuildOutput;
+
+    #[test]
+    fn it_reports_created_and_modified_files_only() {
+        let build_directory = tempfile::tempdir().unwrap();
+        std::fs::write(build_directory.path().join("index.html"), "<p>old</p>\n").unwrap();
+        std::fs::write(build_directory.path().join("same.html"), "same").unwrap();
+
+        let mut output = BuildOutput::new();
+        output.render("index.html", String::from("<p>new</p>\n"), None);
+        output.render("same.html", String::from("same"), None);
+        output.render("blog/post.html", String::from("post"), None);
+
+        let changes = compare(&output, build_directory.path(), false, true);
+
+        assert_eq!(2, changes.len());
+        assert_eq!("blog/post.html", changes[0].path);
+        assert_eq!(Change::Created, changes[0].change);
+        assert_eq!("index.html", changes[1].path);
+        assert_eq!(Change::Modified, changes[1].change);
+
+        let diff = changes[1].diff.as_ref().unwrap();
+        assert!(diff.contains("-<p>old</p>"));
+        assert!(diff.contains("+<p>new</p>"));
+    }
+}
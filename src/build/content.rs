@@ -1,72 +1,343 @@
 use lazy_static::lazy_static;
 use pulldown_cmark::{html, Options, Parser as MarkdownParser};
+use pulldown_cmark::{Event, Tag};
 use regex::Regex;
-use std::fs::File;
-use std::io::prelude::*;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use super::engine::{render_page, Variables};
+use super::links::{rewrite_links, Backlinks, PageLinks, Permalinks};
+use super::navigation::{self, Navigation, NavigationPage};
+use super::output::BuildOutput;
+use crate::configuration::{BuildConfig, CollectionConfig, SortOrder};
 use crate::utils;
 
 pub struct ContentList {
-    pub items: Vec<String>,
+    pub items: Vec<ContentItem>,
 }
 
-pub fn build_content_pages(
-    content_page_template: &Path,
-    content_build_directory: &Path,
-    content_directory: &Path,
-) -> ContentList {
-    log::info!(
-        "Building content pages with template {}",
-        content_page_template.as_os_str().to_str().unwrap()
-    );
+#[derive(Clone)]
+pub struct ContentItem {
+    /// Path of the built page, relative to the build directory and separated by `/`
+    pub href: String,
+    pub frontmatter: BTreeMap<String, String>,
+    /// Number of words of the page, without its code blocks
+    pub word_count: usize,
+    /// Estimated reading time of the page, in minutes
+    pub reading_time: usize,
+    pub render_time: Duration,
+    pub unresolved_wiki_links: Vec<String>,
+    pub headings: Vec<String>,
+    /// Plain text of the page, without its code blocks
+    pub text: String,
+    /// Plain text teaser of the page, see `summarize`
+    pub summary: String,
+}
 
-    // TODO: This should not be specified in the config but should be a combination of two config value
-    // build directory + (new) content page naming (or something like that)
-    // content page naming would refer to:
-    // - listing template name
-    // - path of content pages
-    // - maybe something else
-    create_content_build_folder_if_it_does_not_exist(content_build_directory);
-
-    build_content_files(
-        content_directory,
-        content_build_directory,
-        content_page_template,
-    )
+impl ContentItem {
+    pub fn title(&self) -> Option<&str> {
+        self.frontmatter.get("title").map(String::as_str)
+    }
+
+    /// Name of the series the page is part of, from the `series` frontmatter key.
+    pub fn series(&self) -> Option<&str> {
+        self.frontmatter
+            .get("series")
+            .map(|series| series.trim())
+            .filter(|series| !series.is_empty())
+    }
+
+    /// Tags of the page, from a comma separated `tags` frontmatter key, e.g. `tags: [rust, web]`.
+    pub fn tags(&self) -> Vec<String> {
+        self.frontmatter
+            .get("tags")
+            .map(|tags| {
+                tags.trim_matches(|c| c == '[' || c == ']')
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
-fn build_content_files(
-    content_directory: &Path,
-    content_build_directory: &Path,
-    content_page_template: &Path,
-) -> ContentList {
-    let content_directory_contents = std::fs::read_dir(content_directory)
-        .expect("Could not read contents of contents directory");
+/// Content files of a collection in the collection's order, without its drafts.
+pub struct CollectionFiles {
+    /// Directory the pages are built into, relative to the build directory
+    output_directory: PathBuf,
+    files: Vec<ContentFile>,
+    /// Markdown of every file, once rendered by `render_collection`
+    rendered: Vec<RenderedMarkdown>,
+}
 
-    let mut content_pages = Vec::new();
+/// Standalone Markdown pages of the pages directory, without their drafts.
+pub struct MarkdownPages {
+    pages_directory: PathBuf,
+    files: Vec<ContentFile>,
+    /// Markdown of every file, once rendered by `render_markdown_pages`
+    rendered: Vec<RenderedMarkdown>,
+}
 
-    let content_template = load_content_template(content_page_template);
+/// Markdown of a file rendered to HTML, before its page is built.
+struct RenderedMarkdown {
+    html: String,
+    links: PageLinks,
+    render_time: Duration,
+}
+
+/// Reads the content files of a collection and adds the permalinks of their pages, so content
+/// files link to the pages of every collection.
+pub fn read_collection(
+    collection: &CollectionConfig,
+    build_config: &BuildConfig,
+    permalinks: &mut Permalinks,
+) -> CollectionFiles {
+    let content_directory = Path::new(&collection.content_dir);
+    let content_build_directory = Path::new(&collection.output_directory);
+
+    let mut content_directory_contents: Vec<PathBuf> = std::fs::read_dir(content_directory)
+        .expect("Could not read contents of contents directory")
+        .map(|entry| {
+            entry
+                .expect("Could not read content directory entry")
+                .path()
+        })
+        .collect();
+    content_directory_contents.sort();
+
+    let mut content_files = Vec::new();
+
+    for content_file in content_directory_contents {
+        log::debug!("Reading file {:?}", &content_file);
+
+        if utils::is_plaintext_file(&content_file) {
+            let file = ContentFile::new(&content_file);
+
+            if file.is_draft() && !build_config.drafts {
+                log::info!("Skipping draft {:?}", content_file);
+                permalinks.add_draft(&file.path);
+                continue;
+            }
+
+            content_files.push(file);
+        }
+    }
+
+    sort_content_files(&mut content_files, collection.sort);
 
-    for entry in content_directory_contents.into_iter() {
-        let content_file = entry.unwrap();
+    for file in &content_files {
+        let built_file = BuiltContentFile::from_file_name(content_build_directory, &file.file_name);
+        permalinks.add_page(&file.path, built_file.href, file.title());
+    }
+
+    CollectionFiles {
+        output_directory: content_build_directory.to_path_buf(),
+        files: content_files,
+        rendered: Vec::new(),
+    }
+}
+
+/// Renders the Markdown of every content file of a collection, once the permalinks of every
+/// collection are known, and records their links in `backlinks`.
+pub fn render_collection(
+    collection_files: &mut CollectionFiles,
+    permalinks: &Permalinks,
+    backlinks: &mut Backlinks,
+    output: &mut BuildOutput,
+) {
+    let output_directory = &collection_files.output_directory;
+    let files = collection_files.files.iter().map(|file| {
+        (
+            file,
+            BuiltContentFile::from_file_name(output_directory, &file.file_name).href,
+            file.path.parent().unwrap_or_else(|| Path::new("")),
+        )
+    });
+
+    let rendered = render_markdown(files, permalinks, backlinks, output);
+    collection_files.rendered = rendered;
+}
+
+/// Renders the Markdown of files built into the pages at the given paths, with links relative
+/// to the given directories, and records their links in `backlinks`. Links to missing content
+/// fail the build, unresolved wiki links are reported as warnings.
+fn render_markdown<'a>(
+    files: impl Iterator<Item = (&'a ContentFile, String, &'a Path)>,
+    permalinks: &Permalinks,
+    backlinks: &mut Backlinks,
+    output: &mut BuildOutput,
+) -> Vec<RenderedMarkdown> {
+    let mut rendered = Vec::new();
+    let mut link_errors = Vec::new();
+
+    for (file, href, directory) in files {
+        let started = Instant::now();
+        let (html, links) = convert_markdown_to_html(&file.raw_contents, directory, permalinks);
+
+        report_page_links(&file.path, &links, &mut link_errors, output);
+        backlinks.add(&file.path, &href, &file.title(), &links);
+
+        rendered.push(RenderedMarkdown {
+            html,
+            links,
+            render_time: started.elapsed(),
+        });
+    }
+
+    fail_on_link_errors(&link_errors);
+
+    rendered
+}
+
+/// Reports the links of the content file at `path` to missing content, which fail the build,
+/// and its unresolved wiki links as warnings.
+fn report_page_links(
+    path: &Path,
+    page_links: &PageLinks,
+    link_errors: &mut Vec<String>,
+    output: &mut BuildOutput,
+) {
+    link_errors.extend(
+        page_links
+            .errors
+            .iter()
+            .map(|error| format!("{:?}: {}", path, error)),
+    );
+    for target in &page_links.unresolved_wiki_links {
+        output.warn(format!("{:?}: unresolved wiki link [[{}]]", path, target));
+    }
+}
 
-        log::debug!("Building file {:?}", &content_file.path());
+fn fail_on_link_errors(link_errors: &[String]) {
+    if !link_errors.is_empty() {
+        panic!(
+            "Content links to missing content:\n  {}",
+            link_errors.join("\n  ")
+        );
+    }
+}
 
-        if utils::is_plaintext_file(&content_file.path()) {
-            log::debug!("Markdown file detected, converting to html");
+/// Builds every content file of a collection into its output directory, the returned list
+/// sorted in the collection's order.
+///
+/// Each page links to its neighbours in that order as `{prev}` and `{next}`, and the pages
+/// sharing a `series` frontmatter key to each other as `{series_navigation}`, ordered by their
+/// `series_part` number or their date.
+///
+/// Links to other content files, e.g. `[next](other-post.md)`, `[x](../projects/x.md)` or
+/// `[[Other Post]]`, were rewritten to the permalinks of their pages by `render_collection`.
+/// `{backlinks}` lists the pages of every collection and the Markdown pages linking to a page.
+#[allow(clippy::too_many_arguments)]
+pub fn build_content_pages(
+    collection: &CollectionConfig,
+    collection_files: CollectionFiles,
+    content_page_template: &str,
+    variables: &Variables,
+    build_config: &BuildConfig,
+    base_url: &str,
+    backlinks: &Backlinks,
+    output: &mut BuildOutput,
+) -> ContentList {
+    log::info!("Building content pages of collection {}", collection.name);
 
-            let file = ContentFile::new(&content_file.path());
-            let built_file = file.build(content_template.clone(), content_build_directory);
+    let content_build_directory = Path::new(&collection.output_directory);
+    let content_files = collection_files.files;
+    let rendered_files = collection_files.rendered;
+    let content_template = load_content_template(content_page_template);
 
-            content_pages.push(built_file.file_name);
+    let pages: Vec<NavigationPage> = content_files
+        .iter()
+        .map(|file| NavigationPage {
+            url: utils::join_url(
+                base_url,
+                &BuiltContentFile::from_file_name(content_build_directory, &file.file_name).href,
+            ),
+            title: file.title(),
+        })
+        .collect();
+
+    // Parts of every series, in order
+    let mut series: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, file) in content_files.iter().enumerate() {
+        if let Some(name) = file.series() {
+            series.entry(name).or_default().push(index);
         }
     }
+    for parts in series.values_mut() {
+        parts.sort_by_cached_key(|part| {
+            let file = &content_files[*part];
+            navigation::series_part_key(&file.frontmatter(), &file.file_name)
+        });
+    }
+
+    let mut content_pages = Vec::new();
+
+    for (index, (file, rendered)) in content_files.iter().zip(rendered_files).enumerate() {
+        let started = Instant::now();
+        let navigation = Navigation {
+            backlinks: backlinks.list_html(&file.path),
+            prev: index
+                .checked_sub(1)
+                .map(|prev| navigation::link_html(&pages[prev], "prev"))
+                .unwrap_or_default(),
+            next: pages
+                .get(index + 1)
+                .map(|next| navigation::link_html(next, "next"))
+                .unwrap_or_default(),
+            series: file
+                .series()
+                .map(|name| {
+                    let parts = &series[&name];
+                    let index_path = navigation::series_index_path(content_build_directory, &name);
+
+                    navigation::series_html(
+                        &name,
+                        &utils::join_url(base_url, &utils::to_url_path(&index_path)),
+                        &parts.iter().map(|part| &pages[*part]).collect::<Vec<_>>(),
+                        parts
+                            .iter()
+                            .position(|part| *part == index)
+                            .expect("Every part of a series is listed in it"),
+                    )
+                })
+                .unwrap_or_default(),
+        };
+
+        let metadata = PageMetadata::new(file, build_config);
+        let mut page_variables = variables.clone();
+        metadata.insert_into(&mut page_variables);
+
+        let built_file = file.build(
+            &content_template,
+            page_variables,
+            content_build_directory,
+            &rendered.html,
+            &navigation,
+            output,
+        );
+
+        let (headings, text) = extract_text(&file.raw_contents);
+
+        content_pages.push(ContentItem {
+            href: built_file.href,
+            frontmatter: file.frontmatter(),
+            word_count: metadata.word_count,
+            reading_time: metadata.reading_time,
+            render_time: rendered.render_time + started.elapsed(),
+            unresolved_wiki_links: rendered.links.unresolved_wiki_links,
+            headings,
+            text,
+            summary: metadata.summary,
+        });
+    }
 
     log::info!("Built content pages");
 
     for content_page in &content_pages {
-        log::info!("{}", content_page);
+        log::info!("{}", content_page.href);
     }
 
     ContentList {
@@ -74,11 +345,114 @@ fn build_content_files(
     }
 }
 
+/// Reads the standalone Markdown pages of `pages_directory`, e.g. `about.md`, none if it does
+/// not exist.
+pub fn read_markdown_pages(pages_directory: &Path, build_config: &BuildConfig) -> MarkdownPages {
+    let mut pages = MarkdownPages {
+        pages_directory: pages_directory.to_path_buf(),
+        files: Vec::new(),
+        rendered: Vec::new(),
+    };
+
+    if !pages_directory.is_dir() {
+        log::debug!("No pages directory {:?}", pages_directory);
+        return pages;
+    }
+
+    for path in utils::list_files(pages_directory) {
+        if !utils::is_plaintext_file(&path) {
+            continue;
+        }
+
+        let file = ContentFile::new(&path);
+
+        if file.is_draft() && !build_config.drafts {
+            log::info!("Skipping draft {:?}", path);
+            continue;
+        }
+
+        pages.files.push(file);
+    }
+
+    pages
+}
+
+impl MarkdownPages {
+    /// Directory the page of a file is built into, relative to the build directory.
+    fn output_directory<'a>(&self, file: &'a ContentFile) -> &'a Path {
+        file.path
+            .parent()
+            .and_then(|parent| parent.strip_prefix(&self.pages_directory).ok())
+            .expect("Listed files are inside their directory")
+    }
+}
+
+/// Renders the Markdown of the standalone pages, rewriting their links to content files
+/// relative to `content_directory`, e.g. `[latest post](post.md)` or `[[Post]]`, and records
+/// their links in `backlinks`.
+pub fn render_markdown_pages(
+    pages: &mut MarkdownPages,
+    content_directory: &Path,
+    permalinks: &Permalinks,
+    backlinks: &mut Backlinks,
+    output: &mut BuildOutput,
+) {
+    let files = pages.files.iter().map(|file| {
+        (
+            file,
+            BuiltContentFile::from_file_name(pages.output_directory(file), &file.file_name).href,
+            content_directory,
+        )
+    });
+
+    let rendered = render_markdown(files, permalinks, backlinks, output);
+    pages.rendered = rendered;
+}
+
+/// Builds the standalone Markdown pages at the same path relative to the build directory as in
+/// the pages directory. Unlike content pages, they are not listed anywhere.
+pub fn build_markdown_pages(
+    pages: MarkdownPages,
+    page_template: &str,
+    variables: &Variables,
+    build_config: &BuildConfig,
+    output: &mut BuildOutput,
+) {
+    let page_template = load_content_template(page_template);
+
+    for (file, rendered) in pages.files.iter().zip(&pages.rendered) {
+        log::info!("Building page {:?}", file.path);
+
+        let mut page_variables = variables.clone();
+        PageMetadata::new(file, build_config).insert_into(&mut page_variables);
+
+        file.build(
+            &page_template,
+            page_variables,
+            pages.output_directory(file),
+            &rendered.html,
+            &Navigation::default(),
+            output,
+        );
+    }
+}
+
+/// Whether the `draft` value of a frontmatter marks its content file as a draft, drafts are only
+/// built with `build_config.drafts`.
+pub fn is_draft(draft: Option<&String>) -> bool {
+    draft.map(String::as_str) == Some("true")
+}
+
+/// Frontmatter of the content file at `path`, empty if it has none.
+pub fn read_frontmatter(path: &Path) -> BTreeMap<String, String> {
+    ContentFile::new(path).frontmatter()
+}
+
 struct ContentFile {
-    _path: PathBuf,
+    path: PathBuf,
     file_name: String,
     raw_contents: String,
-    _frontmatter: Option<std::collections::HashMap<String, String>>,
+    frontmatter: Option<std::collections::HashMap<String, String>>,
 }
 
 impl ContentFile {
@@ -94,42 +468,101 @@ impl ContentFile {
         let parsed_content = ContentFileFrontmatterAndRawContent::from_file_contents(file_contents);
 
         ContentFile {
-            _path: path.to_path_buf(),
+            path: path.to_path_buf(),
             file_name: String::from(file_name),
             raw_contents: parsed_content.raw_content,
-            _frontmatter: parsed_content.frontmatter,
+            frontmatter: parsed_content.frontmatter,
         }
     }
 
-    pub fn build(self: &Self, template: String, build_directory: &Path) -> BuiltContentFile {
-        let built_content_file = BuiltContentFile::from_file_name(build_directory, &self.file_name);
+    pub fn frontmatter(&self) -> BTreeMap<String, String> {
+        match &self.frontmatter {
+            Some(frontmatter) => frontmatter.clone().into_iter().collect(),
+            None => BTreeMap::new(),
+        }
+    }
 
-        let mut prepared_template =
-            template.replace("{content}", &convert_markdown_to_html(&self.raw_contents));
+    /// Title of the page, from the frontmatter or the file name otherwise.
+    pub fn title(&self) -> String {
+        self.frontmatter
+            .as_ref()
+            .and_then(|frontmatter| frontmatter.get("title"))
+            .cloned()
+            .unwrap_or_else(|| self.file_name.clone())
+    }
 
-        // Find and replace any {key} with value from frontmatter if some frontmatter was in the
-        // file.
-        match &self._frontmatter {
-            Some(frontmatter) => {
-                for (key, value) in frontmatter.iter() {
-                    log::debug!("Found frontmatter {:?}: {:?}", key, value);
+    /// Name of the series the page is part of, from the `series` frontmatter key.
+    pub fn series(&self) -> Option<String> {
+        self.frontmatter
+            .as_ref()
+            .and_then(|frontmatter| frontmatter.get("series"))
+            .map(|series| series.trim())
+            .filter(|series| !series.is_empty())
+            .map(String::from)
+    }
 
-                    let formatted_key = format!(r#"{{{}}}"#, key);
+    pub fn is_draft(&self) -> bool {
+        match &self.frontmatter {
+            Some(frontmatter) => is_draft(frontmatter.get("draft")),
+            None => false,
+        }
+    }
 
-                    log::debug!("Replacing key {:?} in template", formatted_key);
+    /// Renders the page with the given variables, the frontmatter of the file, escaped like
+    /// any other value, and the `{content}`, `{backlinks}`, `{prev}`, `{next}` and
+    /// `{series_navigation}` HTML.
+    pub fn build(
+        &self,
+        template: &str,
+        mut page_variables: Variables,
+        build_directory: &Path,
+        html: &str,
+        navigation: &Navigation,
+        output: &mut BuildOutput,
+    ) -> BuiltContentFile {
+        let built_content_file = BuiltContentFile::from_file_name(build_directory, &self.file_name);
 
-                    prepared_template = prepared_template.replace(formatted_key.as_str(), value);
-                }
+        if let Some(frontmatter) = &self.frontmatter {
+            for (key, value) in frontmatter.iter() {
+                log::debug!("Found frontmatter {:?}: {:?}", key, value);
+
+                page_variables.insert(key.as_str(), value.as_str());
             }
-            None => {}
         }
 
-        write_content_to_file(&built_content_file, &prepared_template);
+        page_variables.insert_html("content", html);
+        page_variables.insert_html("backlinks", navigation.backlinks.as_str());
+        page_variables.insert_html("prev", navigation.prev.as_str());
+        page_variables.insert_html("next", navigation.next.as_str());
+        page_variables.insert_html("series_navigation", navigation.series.as_str());
+
+        let page = render_page(&built_content_file.path, template, &page_variables, output);
+
+        output.render(&built_content_file.path, page, Some(&self.path));
 
         built_content_file
     }
 }
 
+/// Sorts content files in a collection's order, those without the sorted frontmatter key
+/// coming first.
+fn sort_content_files(content_files: &mut [ContentFile], order: SortOrder) {
+    let key = |file: &ContentFile, name: &str| {
+        file.frontmatter
+            .as_ref()
+            .and_then(|frontmatter| frontmatter.get(name).cloned())
+    };
+
+    match order {
+        SortOrder::FileName => content_files.sort_by(|a, b| a.file_name.cmp(&b.file_name)),
+        SortOrder::Date => content_files.sort_by_key(|file| key(file, "date")),
+        SortOrder::DateDescending => {
+            content_files.sort_by_key(|file| std::cmp::Reverse(key(file, "date")))
+        }
+        SortOrder::Title => content_files.sort_by_key(|file| key(file, "title")),
+    }
+}
+
 struct ContentFileFrontmatterAndRawContent {
     raw_content: String,
     frontmatter: Option<std::collections::HashMap<String, String>>,
@@ -147,19 +580,13 @@ impl ContentFileFrontmatterAndRawContent {
     }
 }
 
-fn load_content_template(content_page_template_path: &Path) -> String {
-    match std::fs::read_to_string(content_page_template_path) {
-        Ok(t) => {
-            // TODO: Validate template
-            validate_content_template(&t).expect("Invalid content template specified");
-            // return template string
-            t
-        }
-        Err(_e) => panic!("Could not load content page template"),
-    }
+fn load_content_template(content_page_template: &str) -> String {
+    validate_content_template(content_page_template).expect("Invalid content template specified");
+
+    String::from(content_page_template)
 }
 
-fn validate_content_template(template: &String) -> Result<(), ()> {
+fn validate_content_template(template: &str) -> Result<(), ()> {
     if template.contains("{content}") {
         Ok(())
     } else {
@@ -167,22 +594,6 @@ fn validate_content_template(template: &String) -> Result<(), ()> {
     }
 }
 
-fn create_content_build_folder_if_it_does_not_exist(content_folder_path: &Path) {
-    match content_folder_path.exists() {
-        true => {}
-        false => {
-            log::debug!(
-                "Creating {}",
-                content_folder_path
-                    .to_str()
-                    .expect("Could not unwrap content folder path")
-            );
-            std::fs::create_dir(content_folder_path)
-                .expect("Failed to create content build folder ");
-        }
-    }
-}
-
 fn remove_frontmatter(file_content: String) -> String {
     lazy_static! {
         static ref FRONTMATTER_REGEX: Regex =
@@ -194,6 +605,7 @@ fn remove_frontmatter(file_content: String) -> String {
     fixed_string
 }
 
+#[allow(clippy::ptr_arg, clippy::needless_return, clippy::needless_borrow)]
 fn parse_frontmatter(
     content_file_content: &String,
 ) -> Option<std::collections::HashMap<String, String>> {
@@ -224,6 +636,7 @@ fn parse_frontmatter(
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::single_match)]
 mod test_frontmatter {
     use super::parse_frontmatter;
 
@@ -341,49 +754,380 @@ fn parse_key_value_pairs(frontmatter: &str) -> Option<std::collections::HashMap<
 
 // fn find_frontmatter(content: &String) -> String {}
 
-fn convert_markdown_to_html(markdown_content: &String) -> String {
+/// Details of a page computed from its Markdown, available to its template as `{summary}`,
+/// `{word_count}` and `{reading_time}`.
+struct PageMetadata {
+    summary: String,
+    word_count: usize,
+    /// Estimated reading time, in minutes
+    reading_time: usize,
+}
+
+impl PageMetadata {
+    fn new(file: &ContentFile, build_config: &BuildConfig) -> Self {
+        let word_count = count_words(&file.raw_contents);
+
+        PageMetadata {
+            summary: summarize(
+                &file.raw_contents,
+                file.frontmatter().get("description").map(String::as_str),
+                build_config.summary_words,
+            ),
+            word_count,
+            reading_time: reading_time(word_count, build_config.words_per_minute),
+        }
+    }
+
+    fn insert_into(&self, variables: &mut Variables) {
+        variables.insert("summary", self.summary.as_str());
+        variables.insert("word_count", self.word_count.to_string());
+        variables.insert("reading_time", self.reading_time.to_string());
+    }
+}
+
+/// Counts the words in the text of a Markdown document, leaving out its markup and code blocks.
+fn count_words(markdown_content: &str) -> usize {
+    let mut in_code_block = false;
+
+    MarkdownParser::new(markdown_content)
+        .map(|event| match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code_block = true;
+                0
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                in_code_block = false;
+                0
+            }
+            Event::Text(text) | Event::Code(text) if !in_code_block => {
+                text.split_whitespace().count()
+            }
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Minutes it takes to read `word_count` words, rounded up.
+fn reading_time(word_count: usize, words_per_minute: usize) -> usize {
+    word_count.div_ceil(words_per_minute.max(1))
+}
+
+/// Extracts the headings and the plain text of a Markdown document, leaving out its code blocks.
+fn extract_text(markdown_content: &str) -> (Vec<String>, String) {
+    let mut headings = Vec::new();
+    let mut heading: Option<String> = None;
+    let mut text = String::new();
+    let mut in_code_block = false;
+
+    for event in MarkdownParser::new(markdown_content) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            Event::Start(Tag::Heading(..)) => heading = Some(String::new()),
+            Event::End(Tag::Heading(..)) => {
+                headings.extend(heading.take());
+                text.push(' ');
+            }
+            Event::Text(fragment) | Event::Code(fragment) if !in_code_block => {
+                if let Some(heading) = &mut heading {
+                    heading.push_str(&fragment);
+                }
+                text.push_str(&fragment);
+            }
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+
+    (
+        headings,
+        text.split_whitespace().collect::<Vec<_>>().join(" "),
+    )
+}
+
+/// Marker ending the summary of a page written in its Markdown.
+const SUMMARY_MARKER: &str = "<!-- more -->";
+
+/// Plain text teaser of a page: its `description`, the text before a `<!-- more -->` marker or
+/// else its first paragraph, cut after `max_words` words.
+fn summarize(markdown_content: &str, description: Option<&str>, max_words: usize) -> String {
+    if let Some(description) = description.filter(|description| !description.trim().is_empty()) {
+        return String::from(description.trim());
+    }
+
+    if let Some((before_marker, _)) = markdown_content.split_once(SUMMARY_MARKER) {
+        return extract_text(before_marker).1;
+    }
+
+    let mut paragraph = String::new();
+    let mut in_paragraph = false;
+
+    for event in MarkdownParser::new(markdown_content) {
+        match event {
+            Event::Start(Tag::Paragraph) => in_paragraph = true,
+            Event::End(Tag::Paragraph) => break,
+            Event::Text(fragment) | Event::Code(fragment) if in_paragraph => {
+                paragraph.push_str(&fragment)
+            }
+            Event::SoftBreak | Event::HardBreak if in_paragraph => paragraph.push(' '),
+            _ => {}
+        }
+    }
+
+    let words: Vec<&str> = paragraph.split_whitespace().collect();
+    match words.len() > max_words {
+        true => format!("{}…", words[..max_words].join(" ")),
+        false => words.join(" "),
+    }
+}
+
+fn markdown_parser(markdown_content: &str) -> MarkdownParser<'_, '_> {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = MarkdownParser::new_ext(markdown_content, options);
-
-    let mut html_output = String::new();
-    html::push_html(&mut html_output, parser);
 
-    html_output
+    MarkdownParser::new_ext(markdown_content, options)
 }
 
-fn write_content_to_file(file_path: &BuiltContentFile, contents: &String) {
-    let mut new_file =
-        File::create(&file_path.path).expect("failed to create a file to store the article");
+/// Converts Markdown to HTML, rewriting links to content files, relative to `directory`, to
+/// their permalinks.
+fn convert_markdown_to_html(
+    markdown_content: &str,
+    directory: &Path,
+    permalinks: &Permalinks,
+) -> (String, PageLinks) {
+    let mut page_links = PageLinks::default();
+    let events = rewrite_links(
+        markdown_parser(markdown_content),
+        directory,
+        permalinks,
+        &mut page_links,
+    );
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
 
-    new_file
-        .write_all(contents.as_bytes())
-        .expect("unable to write to article page");
+    (html_output, page_links)
 }
 
 struct BuiltContentFile {
+    /// Path of the page, relative to the build directory
     path: PathBuf,
-    file_name: String,
+    /// Path of the page separated by `/`, to link to it from the root of the site
+    href: String,
 }
 
 impl BuiltContentFile {
     pub fn from_file_name(build_directory: &Path, file_name: &String) -> Self {
-        let mut path = std::path::PathBuf::new();
-
-        let formatted_path = String::from(format!(
-            "{}/{}.html",
-            build_directory
-                .as_os_str()
-                .to_str()
-                .expect("Could not convert articles build directory in new file path"),
-            file_name
+        let path = build_directory.join(format!("{}.html", file_name));
+        let href = utils::to_url_path(&path);
+
+        Self { path, href }
+    }
+}
+
+#[cfg(test)]
+mod test_text {
+    use super::{count_words, extract_text, reading_time, summarize};
+
+    #[test]
+    fn it_extracts_headings_and_text_without_code_blocks() {
+        let (headings, text) = extract_text(
+            "# Getting *started*\n\nRun `jht build`.\n\n```\nrm -rf /\n```\n\n## Next\nsteps",
+        );
+
+        assert_eq!(vec!["Getting started", "Next"], headings);
+        assert_eq!("Getting started Run jht build. Next steps", text);
+    }
+
+    #[test]
+    fn it_summarizes_with_the_description_the_marker_or_the_first_paragraph() {
+        let markdown = "# Title
+
+First *paragraph*
+of the post.
+
+Second one.";
+
+        assert_eq!("Told", summarize(markdown, Some(" Told "), 50));
+        assert_eq!(
+            "Title Before",
+            summarize(
+                "# Title
+
+Before
+
+<!-- more -->
+
+After",
+                None,
+                50
+            )
+        );
+        assert_eq!(
+            "First paragraph of the post.",
+            summarize(markdown, None, 50)
+        );
+        assert_eq!("First paragraph…", summarize(markdown, Some(""), 2));
+    }
+
+    #[test]
+    fn it_counts_words_without_code_blocks_for_the_reading_time() {
+        let markdown = "# Hello *world*\n\nRun `jht build` now.\n\n```\nlet a = 1;\n```\n";
+
+        assert_eq!(6, count_words(markdown));
+        assert_eq!(0, reading_time(0, 200));
+        assert_eq!(1, reading_time(6, 200));
+        assert_eq!(3, reading_time(401, 200));
+    }
+}
+
+#[cfg(test)]
+mod test_content_file {
+    use super::ContentFile;
+    use crate::build::engine::Variables;
+    use crate::build::navigation::Navigation;
+    use crate::build::output::BuildOutput;
+    use std::path::Path;
+
+    #[test]
+    fn it_escapes_frontmatter_shadowing_html_variables() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("about.md");
+        std::fs::write(
+            &path,
+            "---\nrecent_posts: <script>alert(1)</script>\n---\n\nAbout",
+        )
+        .unwrap();
+
+        let mut variables = Variables::new();
+        variables.insert_html("recent_posts", "<ul></ul>");
+        let mut output = BuildOutput::new();
+
+        ContentFile::new(&path).build(
+            "{recent_posts}",
+            variables,
+            Path::new(""),
+            "",
+            &Navigation::default(),
+            &mut output,
+        );
+
+        let (_, page) = output.files().next().unwrap();
+        assert_eq!(
+            "&lt;script&gt;alert(1)&lt;/script&gt;".as_bytes(),
+            page.read()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_content_links {
+    use crate::build::links::{PageLinks, Permalinks};
+    use std::path::Path;
+
+    fn permalinks() -> Permalinks {
+        let mut permalinks = Permalinks::new("https://example.com/");
+        permalinks.add_draft(Path::new("content/unfinished.md"));
+        permalinks.add_page(
+            Path::new("content/other-post.md"),
+            String::from("blog/other-post.html"),
+            String::from("A Tale of Two Posts"),
+        );
+        permalinks.add_page(
+            Path::new("projects/other-post.md"),
+            String::from("projects/other-post.html"),
+            String::from("Another Project"),
+        );
+        permalinks
+    }
+
+    fn convert_markdown_to_html(
+        markdown_content: &str,
+        permalinks: &Permalinks,
+    ) -> (String, PageLinks) {
+        super::convert_markdown_to_html(markdown_content, Path::new("./content"), permalinks)
+    }
+
+    #[test]
+    fn it_rewrites_links_to_content_files_to_their_permalinks() {
+        let (html, links) = convert_markdown_to_html(
+            "[see this](other-post.md#usage) and [that](./other-post.md)",
+            &permalinks(),
+        );
+
+        assert!(html.contains(r#"href="https://example.com/blog/other-post.html#usage""#));
+        assert!(html.contains(r#"href="https://example.com/blog/other-post.html""#));
+        assert!(links
+            .linked_files
+            .contains(Path::new("content/other-post.md")));
+    }
+
+    #[test]
+    fn it_leaves_other_links_alone() {
+        let (html, links) = convert_markdown_to_html(
+            "[a](https://github.com/readme.md) [b](/notes.md) [c](image.png) [d](#top)",
+            &permalinks(),
+        );
+
+        assert!(html.contains(r#"href="https://github.com/readme.md""#));
+        assert!(html.contains(r#"href="/notes.md""#));
+        assert!(html.contains(r#"href="image.png""#));
+        assert!(html.contains(r##"href="#top""##));
+        assert!(links.linked_files.is_empty());
+    }
+
+    #[test]
+    fn it_reports_links_to_missing_content() {
+        let (_, links) =
+            convert_markdown_to_html("[a](missing.md) [b](unfinished.md)", &permalinks());
+
+        assert_eq!(2, links.errors.len());
+        assert!(links.errors[1].contains("draft"));
+    }
+
+    #[test]
+    fn it_resolves_wiki_links_by_slug_and_title() {
+        let (html, links) = convert_markdown_to_html(
+            "See [[a tale of two posts]], [[other-post|the other one]] and [[Nowhere]].",
+            &permalinks(),
+        );
+
+        assert!(html.contains(
+            r#"<a class="wiki-link" href="https://example.com/blog/other-post.html">a tale of two posts</a>"#
         ));
+        assert!(html.contains(">the other one</a>"));
+        assert!(html.contains("[[Nowhere]]"));
+        assert_eq!(vec![String::from("Nowhere")], links.unresolved_wiki_links);
+    }
 
-        path.push(std::path::Path::new(&formatted_path));
+    #[test]
+    fn it_leaves_wiki_links_in_code_alone() {
+        let (html, links) = convert_markdown_to_html(
+            "`[[other-post]]`\n\n```\n[[other-post]]\n```",
+            &permalinks(),
+        );
+
+        assert!(!html.contains("wiki-link"));
+        assert!(links.linked_files.is_empty());
+    }
 
-        Self {
-            path,
-            file_name: formatted_path,
-        }
+    #[test]
+    fn it_resolves_links_by_path_relative_to_the_content_file() {
+        let (html, links) = convert_markdown_to_html(
+            "[a](../projects/other-post.md) [b](other-post.md) [[other-post]] [[Another Project]]",
+            &permalinks(),
+        );
+
+        assert_eq!(
+            2,
+            html.matches(r#"href="https://example.com/projects/other-post.html""#)
+                .count()
+        );
+        assert_eq!(
+            2,
+            html.matches(r#"href="https://example.com/blog/other-post.html""#)
+                .count()
+        );
+        assert_eq!(2, links.linked_files.len());
     }
 }
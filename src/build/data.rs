@@ -0,0 +1,125 @@
+use serde_json::{Map, Value};
+use std::path::Path;
+
+use crate::utils;
+
+/// Loads every JSON, TOML, YAML and CSV file of the data directory into a table keyed by file
+/// stem, with subdirectories as nested tables, e.g. `data/menus/main.toml` as `menus.main`.
+///
+/// CSV files become an array with a table per row, keyed by the header row.
+pub fn load_data(data_directory: &Path) -> Value {
+    let mut data = Map::new();
+
+    if !data_directory.is_dir() {
+        log::debug!("No data directory {:?}", data_directory);
+        return Value::Object(data);
+    }
+
+    for path in utils::list_files(data_directory) {
+        let value = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => parse(&path, |contents| {
+                serde_json::from_str(contents).map_err(|e| e.to_string())
+            }),
+            Some("toml") => parse(&path, |contents| {
+                toml::from_str(contents).map_err(|e| e.to_string())
+            }),
+            Some("yaml") | Some("yml") => parse(&path, |contents| {
+                serde_yaml::from_str(contents).map_err(|e| e.to_string())
+            }),
+            Some("csv") => parse(&path, parse_csv),
+            _ => {
+                log::warn!("Ignoring data file {:?} of unknown format", path);
+                continue;
+            }
+        };
+
+        log::debug!("Loaded data file {:?}", path);
+
+        let name = path
+            .strip_prefix(data_directory)
+            .expect("Listed files are inside their directory")
+            .with_extension("");
+        let mut keys: Vec<String> = name
+            .iter()
+            .map(|key| key.to_string_lossy().to_string())
+            .collect();
+        let file_key = keys.pop().expect("Data files have a name");
+
+        let table = keys.into_iter().fold(&mut data, |table, key| {
+            table
+                .entry(key)
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .unwrap_or_else(|| panic!("Data file {:?} conflicts with another one", path))
+        });
+
+        if table.insert(file_key, value).is_some() {
+            panic!("Data file {:?} conflicts with another one", path);
+        }
+    }
+
+    Value::Object(data)
+}
+
+fn parse(path: &Path, parser: impl Fn(&str) -> Result<Value, String>) -> Value {
+    let contents = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Could not read data file {:?}: {}", path, e));
+
+    parser(&contents).unwrap_or_else(|e| panic!("Failed to parse data file {:?}: {}", path, e))
+}
+
+fn parse_csv(contents: &str) -> Result<Value, String> {
+    let mut reader = csv::Reader::from_reader(contents.as_bytes());
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| e.to_string())?;
+
+            Ok(Value::Object(
+                headers
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(header, field)| (String::from(header), Value::from(field)))
+                    .collect(),
+            ))
+        })
+        .collect::<Result<Vec<Value>, String>>()
+        .map(Value::Array)
+}
+
+#[cfg(test)]
+mod test_data {
+    use super::load_data;
+
+    #[test]
+    fn it_loads_every_format_by_file_name() {
+        let directory = tempfile::tempdir().unwrap();
+        let menus = directory.path().join("menus");
+        std::fs::create_dir(&menus).unwrap();
+
+        std::fs::write(directory.path().join("site.json"), r#"{"motto": "Hi"}"#).unwrap();
+        std::fs::write(menus.join("main.toml"), "[[items]]\nname = \"Home\"\n").unwrap();
+        std::fs::write(
+            directory.path().join("team.yaml"),
+            "- name: Ada\n- name: Linus\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.path().join("projects.csv"),
+            "name,stars\njust-html,42\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::json!({
+                "site": {"motto": "Hi"},
+                "menus": {"main": {"items": [{"name": "Home"}]}},
+                "team": [{"name": "Ada"}, {"name": "Linus"}],
+                "projects": [{"name": "just-html", "stars": "42"}],
+            }),
+            load_data(directory.path())
+        );
+    }
+}
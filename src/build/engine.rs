@@ -0,0 +1,535 @@
+use lazy_static::lazy_static;
+use pulldown_cmark::escape::escape_html;
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use super::output::BuildOutput;
+
+/// Values a template can use as `{name}`, or `{a.b.c}` for nested ones.
+#[derive(Clone, Debug, Default)]
+pub struct Variables {
+    values: Map<String, Value>,
+    /// Names of the variables holding HTML built by just-html, which is not escaped
+    html: BTreeSet<String>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Variables::default()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.insert_value(&name.into(), &Value::String(value.into()));
+    }
+
+    /// Adds a structured value, whose nested keys templates reach with dots, e.g.
+    /// `{params.social.twitter}`, and whose arrays they can loop over with `{#each name}`.
+    /// Replacing HTML, e.g. with a frontmatter key of the same name, makes it escaped again.
+    pub fn insert_value(&mut self, name: &str, value: &Value) {
+        self.html.retain(|html| {
+            !(html == name
+                || html.starts_with(&format!("{}.", name))
+                || name.starts_with(&format!("{}.", html)))
+        });
+
+        let mut keys: Vec<&str> = name.split('.').collect();
+        let last_key = keys.pop().expect("Split always returns at least one key");
+
+        let table = keys.into_iter().fold(&mut self.values, |table, key| {
+            let entry = table
+                .entry(key)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            entry.as_object_mut().expect("Entry was just made a table")
+        });
+
+        table.insert(String::from(last_key), value.clone());
+    }
+
+    /// Whether there is a variable with this dotted name.
+    pub fn contains(&self, name: &str) -> bool {
+        let mut keys = name.split('.');
+
+        keys.next()
+            .and_then(|first_key| self.values.get(first_key))
+            .and_then(|value| keys.try_fold(value, child))
+            .is_some()
+    }
+
+    /// Adds HTML built by just-html, e.g. the content of a page, inserted as is.
+    pub fn insert_html(&mut self, name: &str, html: impl Into<String>) {
+        self.insert(name, html);
+        self.html.insert(String::from(name));
+    }
+}
+
+/// A rendered template.
+pub struct Rendered {
+    pub text: String,
+    /// Names of the placeholders without a variable, left as they are written
+    pub unresolved: BTreeSet<String>,
+}
+
+enum Node {
+    Text(String),
+    /// `{name}`, or `{name|raw}` to leave the value unescaped, kept as written in `source`
+    /// when there is no such variable
+    Variable {
+        name: String,
+        raw: bool,
+        source: String,
+    },
+    /// `{#each name}...{/each}`, rendering its body once per item of an array
+    Each {
+        name: String,
+        body: Vec<Node>,
+    },
+}
+
+/// Renders a template in a single pass, so values are never substituted again. Every `{name}`
+/// is replaced with the value of the variable, HTML escaped unless written `{name|raw}` or
+/// inserted as HTML, and every `{#each name}...{/each}` block is repeated for each item of the
+/// array, whose keys are variables inside the block along with `{this}`, the item itself, and
+/// `{index}`, its position.
+///
+/// Unknown names are left as they are.
+pub fn render(template: &str, variables: &Variables) -> Rendered {
+    let mut rendered = Rendered {
+        text: String::new(),
+        unresolved: BTreeSet::new(),
+    };
+
+    render_nodes(&parse(template), &[], variables, &mut rendered);
+
+    rendered
+}
+
+/// Renders the template of the page at `path`, warning about placeholders left unresolved.
+pub fn render_page(
+    path: &Path,
+    template: &str,
+    variables: &Variables,
+    output: &mut BuildOutput,
+) -> String {
+    let rendered = render(template, variables);
+
+    for name in &rendered.unresolved {
+        output.warn(format!("{:?}: unresolved placeholder {{{}}}", path, name));
+    }
+
+    rendered.text
+}
+
+/// Block of a template being parsed.
+struct Block {
+    /// Name and source of the `{#each}` tag opening the block, none for the template itself
+    each: Option<(String, String)>,
+    nodes: Vec<Node>,
+}
+
+fn parse(template: &str) -> Vec<Node> {
+    lazy_static! {
+        static ref TAG_REGEX: Regex = Regex::new(
+            r"\{(?:#each\s+(?P<each>[A-Za-z_][\w.-]*)|(?P<end>/each)|(?P<name>[A-Za-z_][\w.-]*)(?P<raw>\s*\|\s*raw)?)\}"
+        )
+        .unwrap();
+    }
+
+    let mut blocks = vec![Block {
+        each: None,
+        nodes: Vec::new(),
+    }];
+    let mut last_end = 0;
+
+    for captures in TAG_REGEX.captures_iter(template) {
+        let tag = captures.get(0).expect("Captures always have a whole match");
+
+        push_text(&mut blocks, &template[last_end..tag.start()]);
+        last_end = tag.end();
+
+        if let Some(name) = captures.name("name") {
+            current_nodes(&mut blocks).push(Node::Variable {
+                name: String::from(name.as_str()),
+                raw: captures.name("raw").is_some(),
+                source: String::from(tag.as_str()),
+            });
+        } else if let Some(name) = captures.name("each") {
+            blocks.push(Block {
+                each: Some((String::from(name.as_str()), String::from(tag.as_str()))),
+                nodes: Vec::new(),
+            });
+        } else if blocks.len() > 1 {
+            let block = blocks.pop().expect("There is more than one block");
+            let (name, _) = block.each.expect("Only the template block has no each tag");
+
+            current_nodes(&mut blocks).push(Node::Each {
+                name,
+                body: block.nodes,
+            });
+        } else {
+            log::warn!("Found {} without a matching {{#each}}", tag.as_str());
+            push_text(&mut blocks, tag.as_str());
+        }
+    }
+
+    push_text(&mut blocks, &template[last_end..]);
+
+    // Keep unclosed blocks as they are written
+    while blocks.len() > 1 {
+        let block = blocks.pop().expect("There is more than one block");
+        let (name, source) = block.each.expect("Only the template block has no each tag");
+        log::warn!("Found {{#each {}}} without a matching {{/each}}", name);
+
+        push_text(&mut blocks, &source);
+        current_nodes(&mut blocks).extend(block.nodes);
+    }
+
+    blocks
+        .pop()
+        .expect("The template block is never closed")
+        .nodes
+}
+
+fn current_nodes(blocks: &mut [Block]) -> &mut Vec<Node> {
+    &mut blocks
+        .last_mut()
+        .expect("The template block is never closed")
+        .nodes
+}
+
+fn push_text(blocks: &mut [Block], text: &str) {
+    if !text.is_empty() {
+        current_nodes(blocks).push(Node::Text(String::from(text)));
+    }
+}
+
+/// Item of an enclosing `{#each}` block, whose keys are variables inside it along with `{this}`
+/// and `{index}`.
+#[derive(Clone, Copy)]
+struct LoopItem<'a> {
+    value: &'a Value,
+    index: usize,
+}
+
+impl<'a> LoopItem<'a> {
+    fn get(&self, key: &str) -> Option<Cow<'a, Value>> {
+        match key {
+            "this" => Some(Cow::Borrowed(self.value)),
+            "index" => Some(Cow::Owned(Value::from(self.index))),
+            key => self.value.get(key).map(Cow::Borrowed),
+        }
+    }
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    loop_items: &[LoopItem],
+    variables: &Variables,
+    rendered: &mut Rendered,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => rendered.text.push_str(text),
+            Node::Variable { name, raw, source } => {
+                match lookup(&variables.values, loop_items, name) {
+                    // Only the variables outside of loops hold HTML, loop items are always data
+                    Some(value)
+                        if *raw || (loop_items.is_empty() && variables.html.contains(name)) =>
+                    {
+                        rendered.text.push_str(&to_text(&value))
+                    }
+                    Some(value) => escape_html(&mut rendered.text, &to_text(&value))
+                        .expect("Writing to a string cannot fail"),
+                    None => {
+                        rendered.unresolved.insert(name.clone());
+                        rendered.text.push_str(source);
+                    }
+                }
+            }
+            Node::Each { name, body } => {
+                let items = match lookup(&variables.values, loop_items, name) {
+                    Some(value) => value,
+                    None => {
+                        rendered.unresolved.insert(name.clone());
+                        continue;
+                    }
+                };
+
+                for (index, item) in items_of(&items).into_iter().enumerate() {
+                    let mut item_scopes = loop_items.to_vec();
+                    item_scopes.push(LoopItem { value: item, index });
+                    render_nodes(body, &item_scopes, variables, rendered);
+                }
+            }
+        }
+    }
+}
+
+/// Items a `{#each}` block repeats for: those of an array, or the values of a table.
+fn items_of(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(table) => table.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Finds a variable by its dotted name, in the innermost loop item defining its first key, or
+/// among the variables otherwise.
+fn lookup<'a>(
+    values: &'a Map<String, Value>,
+    loop_items: &[LoopItem<'a>],
+    name: &str,
+) -> Option<Cow<'a, Value>> {
+    let mut keys = name.split('.');
+    let first_key = keys.next()?;
+
+    let value = loop_items
+        .iter()
+        .rev()
+        .find_map(|item| item.get(first_key))
+        .or_else(|| values.get(first_key).map(Cow::Borrowed))?;
+
+    match value {
+        Cow::Borrowed(value) => keys.try_fold(value, child).map(Cow::Borrowed),
+        // `{index}` has no keys
+        Cow::Owned(value) => keys.next().is_none().then_some(Cow::Owned(value)),
+    }
+}
+
+/// Value of a table's key, or an array's item by index.
+fn child<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+        value => value.get(key),
+    }
+}
+
+/// Names of the variables a template uses, leaving out the keys of the items its loops repeat
+/// for, as found in `variables`, and `{this}` and `{index}`.
+pub fn used_variables(template: &str, variables: &Variables) -> BTreeSet<String> {
+    let mut used = BTreeSet::new();
+    collect_variables(&parse(template), &variables.values, &[], &mut used);
+    used
+}
+
+/// `loops` holds the items of every enclosing loop, innermost last.
+fn collect_variables<'a>(
+    nodes: &[Node],
+    values: &'a Map<String, Value>,
+    loops: &[Vec<&'a Value>],
+    used: &mut BTreeSet<String>,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Variable { name, .. } => {
+                if !is_item_key(loops, name) {
+                    used.insert(name.clone());
+                }
+            }
+            Node::Each { name, body } => {
+                if !is_item_key(loops, name) {
+                    used.insert(name.clone());
+                }
+
+                let mut inner_loops = loops.to_vec();
+                inner_loops.push(
+                    loop_values(values, loops, name)
+                        .into_iter()
+                        .flat_map(items_of)
+                        .collect(),
+                );
+                collect_variables(body, values, &inner_loops, used);
+            }
+        }
+    }
+}
+
+/// Whether the first key of a name is defined by the items of the enclosing loops.
+fn is_item_key(loops: &[Vec<&Value>], name: &str) -> bool {
+    let first_key = name.split('.').next().unwrap_or(name);
+
+    !loops.is_empty()
+        && (first_key == "this"
+            || first_key == "index"
+            || loops
+                .iter()
+                .flatten()
+                .any(|item| item.get(first_key).is_some()))
+}
+
+/// Values a dotted name can have inside the enclosing loops, one per item of the innermost loop
+/// defining its first key, or the variable's value otherwise.
+fn loop_values<'a>(
+    values: &'a Map<String, Value>,
+    loops: &[Vec<&'a Value>],
+    name: &str,
+) -> Vec<&'a Value> {
+    let mut keys = name.split('.');
+    let first_key = keys.next().unwrap_or(name);
+    let keys: Vec<&str> = keys.collect();
+
+    let found = loops
+        .iter()
+        .rev()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| match first_key {
+                    "this" => Some(*item),
+                    key => item.get(key),
+                })
+                .collect::<Vec<_>>()
+        })
+        .find(|found| !found.is_empty())
+        .unwrap_or_else(|| values.get(first_key).into_iter().collect());
+
+    found
+        .into_iter()
+        .filter_map(|value| keys.iter().try_fold(value, |value, key| child(value, key)))
+        .collect()
+}
+
+/// Text of a value as shown in a template.
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(to_text).collect::<Vec<_>>().join(", "),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test_engine {
+    use super::{render, used_variables, Variables};
+
+    #[test]
+    fn it_replaces_known_variables_only() {
+        let mut variables = Variables::new();
+        variables.insert("site.title", "My site");
+
+        let rendered = render(
+            "<h1>{site.title}</h1> {unknown} body { margin: 0 }",
+            &variables,
+        );
+
+        assert_eq!(
+            "<h1>My site</h1> {unknown} body { margin: 0 }",
+            rendered.text
+        );
+        assert_eq!(
+            vec!["unknown"],
+            rendered.unresolved.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn it_escapes_values_unless_raw_or_html() {
+        let mut variables = Variables::new();
+        variables.insert("title", "Fish & <Chips>");
+        variables.insert_html("content", "<p>Hi</p>");
+
+        assert_eq!(
+            "Fish &amp; &lt;Chips&gt; Fish & <Chips> <p>Hi</p>",
+            render("{title} {title | raw} {content}", &variables).text
+        );
+    }
+
+    #[test]
+    fn it_escapes_values_replacing_html() {
+        let mut variables = Variables::new();
+        variables.insert_html("recent_posts", "<ul></ul>");
+        variables.insert("recent_posts", "<script>alert(1)</script>");
+
+        assert_eq!(
+            "&lt;script&gt;alert(1)&lt;/script&gt;",
+            render("{recent_posts}", &variables).text
+        );
+    }
+
+    #[test]
+    fn it_does_not_substitute_values_again() {
+        let mut variables = Variables::new();
+        variables.insert("title", "{content}");
+        variables.insert_html("content", "<p>Secret</p>");
+
+        assert_eq!(
+            "{content} <p>Secret</p>",
+            render("{title} {content}", &variables).text
+        );
+    }
+
+    #[test]
+    fn it_reaches_nested_values_with_dots() {
+        let mut variables = Variables::new();
+        variables.insert_value(
+            "params",
+            &serde_json::json!({"social": {"twitter": "@jht"}, "tags": ["a", "b"], "year": 2022}),
+        );
+
+        assert_eq!(
+            "@jht a, b b 2022",
+            render(
+                "{params.social.twitter} {params.tags} {params.tags.1} {params.year}",
+                &variables
+            )
+            .text
+        );
+    }
+
+    #[test]
+    fn it_repeats_each_blocks_for_every_item() {
+        let mut variables = Variables::new();
+        variables.insert("site.title", "Team");
+        variables.insert_value(
+            "data.team",
+            &serde_json::json!([{"name": "Ada", "skills": ["maths"]}, {"name": "Linus"}]),
+        );
+        variables.insert_value("data.tags", &serde_json::json!(["rust", "web"]));
+
+        assert_eq!(
+            "<li>0 Ada (Team)</li><li>1 Linus (Team)</li> #rust #web ",
+            render(
+                "{#each data.team}<li>{index} {name} ({site.title})</li>{/each} \
+                 {#each data.tags}#{this} {/each}",
+                &variables
+            )
+            .text
+        );
+    }
+
+    #[test]
+    fn it_lists_the_variables_used_but_not_the_keys_of_loop_items() {
+        let mut variables = Variables::new();
+        variables.insert_value(
+            "data.team",
+            &serde_json::json!([{"name": "Ada", "skills": ["maths"]}, {"name": "Linus"}]),
+        );
+
+        assert_eq!(
+            vec!["content", "data.team", "site.title", "title"],
+            used_variables(
+                "{title} {content|raw} {#each data.team}{index} {name} {site.title} \
+                 {#each skills}{this}{/each}{/each} {title}",
+                &variables
+            )
+            .into_iter()
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn it_keeps_unmatched_each_tags_as_written() {
+        assert_eq!(
+            "{/each} {#each open} {title}",
+            render("{/each} {#each open} {title}", &Variables::new()).text
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use chrono::{DateTime, NaiveDate};
+use pulldown_cmark::escape::escape_html;
+
+use super::content::{ContentItem, ContentList};
+use super::output::BuildOutput;
+use crate::configuration::{CollectionConfig, SiteConfig};
+use crate::utils;
+
+/// Writes the Atom feed of a collection to its `feed` path, if it has one, with an entry per
+/// page in the collection's order.
+pub fn build_feed(
+    collection: &CollectionConfig,
+    content_list: &ContentList,
+    site: &SiteConfig,
+    build_time: &str,
+    output: &mut BuildOutput,
+) {
+    let feed_path = match &collection.feed {
+        Some(feed_path) => feed_path,
+        None => return,
+    };
+
+    log::info!("Building feed {:?}", feed_path);
+
+    output.render(
+        feed_path,
+        atom_feed(collection, content_list, site, build_time),
+        None,
+    );
+}
+
+fn atom_feed(
+    collection: &CollectionConfig,
+    content_list: &ContentList,
+    site: &SiteConfig,
+    build_time: &str,
+) -> String {
+    let feed_url = utils::join_url(&site.base_url, collection.feed.as_deref().unwrap_or(""));
+    let updated = content_list
+        .items
+        .iter()
+        .filter_map(entry_date)
+        .max()
+        .unwrap_or_else(|| String::from(build_time));
+
+    let mut feed = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    feed.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    push_element(
+        &mut feed,
+        "  ",
+        "title",
+        &format!("{} - {}", site.title, collection.name),
+    );
+    push_element(&mut feed, "  ", "id", &feed_url);
+    push_link(&mut feed, "  ", &feed_url, Some("self"));
+    push_link(&mut feed, "  ", &site.base_url, None);
+    push_element(&mut feed, "  ", "updated", &updated);
+    if !site.author.is_empty() {
+        feed.push_str("  <author>\n");
+        push_element(&mut feed, "    ", "name", &site.author);
+        feed.push_str("  </author>\n");
+    }
+
+    for item in &content_list.items {
+        let url = utils::join_url(&site.base_url, &item.href);
+
+        feed.push_str("  <entry>\n");
+        push_element(
+            &mut feed,
+            "    ",
+            "title",
+            item.title().unwrap_or(&item.href),
+        );
+        push_element(&mut feed, "    ", "id", &url);
+        push_link(&mut feed, "    ", &url, None);
+        push_element(
+            &mut feed,
+            "    ",
+            "updated",
+            &entry_date(item).unwrap_or_else(|| String::from(build_time)),
+        );
+        if !item.summary.is_empty() {
+            push_element(&mut feed, "    ", "summary", &item.summary);
+        }
+        feed.push_str("  </entry>\n");
+    }
+
+    feed.push_str("</feed>\n");
+
+    feed
+}
+
+/// Date of a page as an RFC 3339 timestamp, from its `date` frontmatter key written as a date,
+/// e.g. `2022-08-01`, or as a timestamp.
+fn entry_date(item: &ContentItem) -> Option<String> {
+    let date = item.frontmatter.get("date")?.trim();
+
+    DateTime::parse_from_rfc3339(date)
+        .map(|date| date.to_rfc3339())
+        .or_else(|_| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map(|date| format!("{}T00:00:00+00:00", date.format("%Y-%m-%d")))
+        })
+        .ok()
+}
+
+fn push_element(feed: &mut String, indent: &str, name: &str, text: &str) {
+    feed.push_str(&format!("{}<{}>", indent, name));
+    escape_html(&mut *feed, text).expect("Writing to a string cannot fail");
+    feed.push_str(&format!("</{}>\n", name));
+}
+
+fn push_link(feed: &mut String, indent: &str, href: &str, rel: Option<&str>) {
+    feed.push_str(indent);
+    feed.push_str("<link href=\"");
+    escape_html(&mut *feed, href).expect("Writing to a string cannot fail");
+    feed.push('"');
+    if let Some(rel) = rel {
+        feed.push_str(&format!(" rel=\"{}\"", rel));
+    }
+    feed.push_str("/>\n");
+}
+
+#[cfg(test)]
+mod test_feed {
+    use super::atom_feed;
+    use crate::build::content::{ContentItem, ContentList};
+    use crate::configuration::{CollectionConfig, SiteConfig};
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    fn item(href: &str, title: &str, date: &str) -> ContentItem {
+        ContentItem {
+            href: String::from(href),
+            frontmatter: BTreeMap::from([
+                (String::from("title"), String::from(title)),
+                (String::from("date"), String::from(date)),
+            ]),
+            word_count: 0,
+            reading_time: 0,
+            render_time: Duration::ZERO,
+            unresolved_wiki_links: Vec::new(),
+            headings: Vec::new(),
+            text: String::new(),
+            summary: format!("About {}", title),
+        }
+    }
+
+    #[test]
+    fn it_lists_every_page_with_its_date() {
+        let collection = CollectionConfig {
+            name: String::from("projects"),
+            feed: Some(String::from("projects.xml")),
+            ..CollectionConfig::default()
+        };
+        let site = SiteConfig {
+            title: String::from("Tom & Co"),
+            base_url: String::from("https://example.com/"),
+            ..SiteConfig::default()
+        };
+        let content_list = ContentList {
+            items: vec![
+                item("projects/b.html", "B <2>", "2022-08-02"),
+                item("projects/a.html", "A", "soon"),
+            ],
+        };
+
+        assert_eq!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<feed xmlns=\"http://www.w3.org/2005/Atom\">
+  <title>Tom &amp; Co - projects</title>
+  <id>https://example.com/projects.xml</id>
+  <link href=\"https://example.com/projects.xml\" rel=\"self\"/>
+  <link href=\"https://example.com/\"/>
+  <updated>2022-08-02T00:00:00+00:00</updated>
+  <entry>
+    <title>B &lt;2&gt;</title>
+    <id>https://example.com/projects/b.html</id>
+    <link href=\"https://example.com/projects/b.html\"/>
+    <updated>2022-08-02T00:00:00+00:00</updated>
+    <summary>About B &lt;2&gt;</summary>
+  </entry>
+  <entry>
+    <title>A</title>
+    <id>https://example.com/projects/a.html</id>
+    <link href=\"https://example.com/projects/a.html\"/>
+    <updated>2022-09-01T00:00:00+00:00</updated>
+    <summary>About A</summary>
+  </entry>
+</feed>
+",
+            atom_feed(
+                &collection,
+                &content_list,
+                &site,
+                "2022-09-01T00:00:00+00:00"
+            )
+        );
+    }
+}
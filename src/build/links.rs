@@ -0,0 +1,337 @@
+use lazy_static::lazy_static;
+use pulldown_cmark::escape::{escape_href, escape_html};
+use pulldown_cmark::{Event, Tag};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+
+use crate::utils;
+
+/// Page a content or Markdown page file is built into.
+struct LinkedPage {
+    /// Path of the page, relative to the build directory and separated by `/`
+    href: String,
+    title: String,
+}
+
+/// Permalinks of the content pages, by path of their content file, to resolve the links between
+/// content files of every collection.
+pub struct Permalinks {
+    base_url: String,
+    pages: BTreeMap<PathBuf, LinkedPage>,
+    /// Content files left out of the build as drafts
+    drafts: BTreeSet<PathBuf>,
+    /// Paths by file stem, and by lowercase title, for wiki links
+    stems: BTreeMap<String, Vec<PathBuf>>,
+    titles: BTreeMap<String, Vec<PathBuf>>,
+}
+
+/// Link to a content file, resolved to the permalink of its page.
+pub struct ResolvedLink {
+    pub path: PathBuf,
+    pub url: String,
+}
+
+/// Links between content files found while rendering a page.
+#[derive(Default)]
+pub struct PageLinks {
+    /// Paths of the content files the page links to
+    pub linked_files: BTreeSet<PathBuf>,
+    pub unresolved_wiki_links: Vec<String>,
+    /// Links to content files that are not built, which fail the build
+    pub errors: Vec<String>,
+}
+
+impl Permalinks {
+    pub fn new(base_url: &str) -> Self {
+        Permalinks {
+            base_url: String::from(base_url),
+            pages: BTreeMap::new(),
+            drafts: BTreeSet::new(),
+            stems: BTreeMap::new(),
+            titles: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_page(&mut self, path: &Path, href: String, title: String) {
+        let path = normalize_path(path);
+
+        if let Some(stem) = path.file_stem() {
+            self.stems
+                .entry(stem.to_string_lossy().to_string())
+                .or_default()
+                .push(path.clone());
+        }
+        self.titles
+            .entry(title.to_lowercase())
+            .or_default()
+            .push(path.clone());
+        self.pages.insert(path, LinkedPage { href, title });
+    }
+
+    pub fn add_draft(&mut self, path: &Path) {
+        self.drafts.insert(normalize_path(path));
+    }
+
+    fn link_to(&self, path: &Path, suffix: &str) -> Option<ResolvedLink> {
+        self.pages.get(path).map(|page| ResolvedLink {
+            path: path.to_path_buf(),
+            url: format!("{}{}", utils::join_url(&self.base_url, &page.href), suffix),
+        })
+    }
+
+    /// Resolves a Markdown link to a content file, e.g. `other-post.md#usage` or
+    /// `../projects/jht.md`, relative to `directory`. Returns `None` for links that do not point
+    /// to a Markdown file.
+    pub fn resolve(
+        &self,
+        directory: &Path,
+        destination: &str,
+    ) -> Option<Result<ResolvedLink, String>> {
+        if utils::is_external_url(destination) || destination.starts_with('/') {
+            return None;
+        }
+
+        let (path, suffix) =
+            destination.split_at(destination.find(['#', '?']).unwrap_or(destination.len()));
+
+        if !path.ends_with(".md") {
+            return None;
+        }
+
+        let path = normalize_path(&directory.join(path));
+
+        Some(match self.link_to(&path, suffix) {
+            Some(link) => Ok(link),
+            None if self.drafts.contains(&path) => Err(format!(
+                "{:?} links to a draft, which is not built",
+                destination
+            )),
+            None => Err(format!(
+                "{:?} links to a content file that does not exist",
+                destination
+            )),
+        })
+    }
+
+    /// Resolves the target of a wiki link, e.g. `hello-world` or `Hello, World!#usage`, by file
+    /// stem first and by title otherwise. When several content files match, the one in
+    /// `directory` wins, otherwise the link is left unresolved.
+    pub fn resolve_wiki_link(&self, directory: &Path, target: &str) -> Option<ResolvedLink> {
+        let (name, suffix) = target.split_at(target.find('#').unwrap_or(target.len()));
+        let name = name.trim();
+
+        let candidates = self
+            .stems
+            .get(name)
+            .or_else(|| self.stems.get(&utils::slugify(name)))
+            .or_else(|| self.titles.get(&name.to_lowercase()))?;
+        let directory = normalize_path(directory);
+
+        let path = match candidates.as_slice() {
+            [path] => path,
+            _ => candidates
+                .iter()
+                .find(|path| path.parent() == Some(directory.as_path()))?,
+        };
+
+        self.link_to(path, suffix)
+    }
+}
+
+/// Pages linking to each content file, from every collection and the Markdown pages.
+pub struct Backlinks {
+    base_url: String,
+    /// Linking pages by path of their file, by path of the content file they link to
+    linking_pages: BTreeMap<PathBuf, BTreeMap<PathBuf, LinkedPage>>,
+}
+
+impl Backlinks {
+    pub fn new(base_url: &str) -> Self {
+        Backlinks {
+            base_url: String::from(base_url),
+            linking_pages: BTreeMap::new(),
+        }
+    }
+
+    /// Records the links of the file at `path`, built into the page at `href`.
+    pub fn add(&mut self, path: &Path, href: &str, title: &str, links: &PageLinks) {
+        let path = normalize_path(path);
+
+        for linked_file in links.linked_files.iter().filter(|linked| **linked != path) {
+            self.linking_pages
+                .entry(linked_file.clone())
+                .or_default()
+                .insert(
+                    path.clone(),
+                    LinkedPage {
+                        href: String::from(href),
+                        title: String::from(title),
+                    },
+                );
+        }
+    }
+
+    /// Lists links to the pages linking to the content file at `path`, empty if there are none.
+    pub fn list_html(&self, path: &Path) -> String {
+        let linking_pages = match self.linking_pages.get(&normalize_path(path)) {
+            Some(linking_pages) => linking_pages,
+            None => return String::new(),
+        };
+
+        let mut list = String::from("<ul class=\"backlinks\">");
+
+        for page in linking_pages.values() {
+            list.push_str("<li><a href=\"");
+            escape_href(&mut list, &utils::join_url(&self.base_url, &page.href))
+                .expect("Writing to a string cannot fail");
+            list.push_str("\">");
+            escape_html(&mut list, &page.title).expect("Writing to a string cannot fail");
+            list.push_str("</a></li>");
+        }
+
+        list.push_str("</ul>");
+        list
+    }
+}
+
+/// Path of a content file with its `.` and `..` components resolved, to compare the paths of
+/// links with the paths of the pages.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                _ => normalized.push(component),
+            },
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Rewrites the Markdown links to other content files to their permalinks and turns wiki
+/// links, `[[Page Title]]` or `[[slug|label]]`, into links, recording what the page links to.
+/// Links are relative to `directory`.
+pub fn rewrite_links<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    directory: &Path,
+    permalinks: &Permalinks,
+    links: &mut PageLinks,
+) -> Vec<Event<'a>> {
+    let mut rewritten = Vec::new();
+    // The parser splits text around brackets, so wiki links are looked for in whole runs of text
+    let mut text = String::new();
+    let mut in_code_block = false;
+
+    for event in events {
+        if let Event::Text(fragment) = &event {
+            if !in_code_block {
+                text.push_str(fragment);
+                continue;
+            }
+        }
+
+        expand_wiki_links(&mut text, directory, permalinks, links, &mut rewritten);
+
+        let event = match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                in_code_block = true;
+                Event::Start(Tag::CodeBlock(kind))
+            }
+            Event::End(Tag::CodeBlock(kind)) => {
+                in_code_block = false;
+                Event::End(Tag::CodeBlock(kind))
+            }
+            Event::Start(Tag::Link(link_type, destination, title)) => {
+                let destination = match permalinks.resolve(directory, &destination) {
+                    Some(Ok(link)) => {
+                        links.linked_files.insert(link.path);
+                        link.url.into()
+                    }
+                    Some(Err(error)) => {
+                        links.errors.push(error);
+                        destination
+                    }
+                    None => destination,
+                };
+
+                Event::Start(Tag::Link(link_type, destination, title))
+            }
+            event => event,
+        };
+
+        rewritten.push(event);
+    }
+
+    expand_wiki_links(&mut text, directory, permalinks, links, &mut rewritten);
+
+    rewritten
+}
+
+/// Turns the wiki links of a run of text into links, leaving unresolved ones as they are, and
+/// empties the text.
+fn expand_wiki_links(
+    text: &mut String,
+    directory: &Path,
+    permalinks: &Permalinks,
+    links: &mut PageLinks,
+    events: &mut Vec<Event>,
+) {
+    lazy_static! {
+        static ref WIKI_LINK_REGEX: Regex =
+            Regex::new(r"\[\[(?P<target>[^\[\]|]+)(?:\|(?P<label>[^\[\]]+))?\]\]").unwrap();
+    }
+
+    if text.is_empty() {
+        return;
+    }
+
+    let mut last_end = 0;
+
+    for captures in WIKI_LINK_REGEX.captures_iter(text) {
+        let wiki_link = captures.get(0).expect("Captures always have a whole match");
+        let target = captures["target"].trim();
+        let label = captures
+            .name("label")
+            .map(|label| label.as_str().trim())
+            .unwrap_or(target);
+
+        if wiki_link.start() > last_end {
+            events.push(Event::Text(
+                text[last_end..wiki_link.start()].to_string().into(),
+            ));
+        }
+
+        match permalinks.resolve_wiki_link(directory, target) {
+            Some(link) => {
+                let mut html = String::from("<a class=\"wiki-link\" href=\"");
+                escape_href(&mut html, &link.url).expect("Writing to a string cannot fail");
+                html.push_str("\">");
+                escape_html(&mut html, label).expect("Writing to a string cannot fail");
+                html.push_str("</a>");
+
+                links.linked_files.insert(link.path);
+                events.push(Event::Html(html.into()));
+            }
+            None => {
+                links.unresolved_wiki_links.push(String::from(target));
+                events.push(Event::Text(wiki_link.as_str().to_string().into()));
+            }
+        }
+
+        last_end = wiki_link.end();
+    }
+
+    if last_end < text.len() {
+        events.push(Event::Text(text[last_end..].to_string().into()));
+    }
+
+    text.clear();
+}
@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use super::{content, engine, templates, theme};
+use crate::configuration::Config;
+use crate::utils;
+
+/// Variables used by the templates of a site and the problems found with them.
+pub struct TemplateLint {
+    /// Variables used by each template, by template name
+    pub usages: BTreeMap<String, BTreeSet<String>>,
+    pub problems: Vec<String>,
+}
+
+/// Content or page files rendered with a template, and their frontmatter.
+type Frontmatters = Vec<(PathBuf, BTreeMap<String, String>)>;
+
+/// Checks that every variable used by the templates is defined: by the site, by a default or,
+/// for the templates of content files and pages, by the frontmatter of each of these files.
+pub fn lint_templates(config: &Config) -> TemplateLint {
+    let theme = theme::Theme::load(config);
+    let templates = templates::Templates::new(config, theme.as_ref());
+    let collections = config.collections();
+
+    let mut lint = TemplateLint {
+        usages: BTreeMap::new(),
+        problems: Vec::new(),
+    };
+
+    // The same variables as the build, HTML values left empty
+    let mut variables = super::site_variables(config);
+    let mut file_variables = variables.clone();
+    file_variables.insert_html("content", "");
+    file_variables.insert("summary", "");
+    file_variables.insert("word_count", "");
+    file_variables.insert("reading_time", "");
+    file_variables.insert_html("backlinks", "");
+    file_variables.insert_html("prev", "");
+    file_variables.insert_html("next", "");
+    file_variables.insert_html("series_navigation", "");
+
+    let collection_files: Vec<Frontmatters> = collections
+        .iter()
+        .map(|collection| {
+            read_frontmatters(
+                Path::new(&collection.content_dir),
+                false,
+                config.build_config.drafts,
+            )
+        })
+        .collect();
+
+    for (collection, files) in collections.iter().zip(&collection_files) {
+        let content_template_path = Path::new(&collection.content_template);
+        let content_template = templates
+            .load_path(content_template_path)
+            .expect("Could not load content page template");
+        lint.check_file_template(
+            &template_name(&templates, content_template_path),
+            &content_template,
+            &file_variables,
+            files,
+        );
+    }
+
+    variables.insert_html("recent_posts", "");
+    file_variables.insert_html("recent_posts", "");
+
+    let pages = read_frontmatters(
+        Path::new(&config.pages_directory),
+        true,
+        config.build_config.drafts,
+    );
+    if let Some(page_template) = templates.load(Path::new(super::PAGE_TEMPLATE)) {
+        lint.check_file_template(
+            super::PAGE_TEMPLATE,
+            &page_template,
+            &file_variables,
+            &pages,
+        );
+    } else if !pages.is_empty() {
+        let content_template_path = Path::new(&config.content_template);
+        let content_template = templates
+            .load_path(content_template_path)
+            .expect("Could not load content page template");
+        lint.check_file_template(
+            &template_name(&templates, content_template_path),
+            &content_template,
+            &file_variables,
+            &pages,
+        );
+    }
+
+    lint.check_template(
+        "index.html",
+        &templates
+            .load(Path::new("index.html"))
+            .expect("index.html template missing"),
+        &variables,
+    );
+
+    // Loops over `content_items` see the keys of every file of the collection
+    let mut series_items = Vec::new();
+    for (collection, files) in collections.iter().zip(&collection_files) {
+        let items: Vec<serde_json::Value> = files
+            .iter()
+            .map(|(_, frontmatter)| content_item(frontmatter))
+            .collect();
+        let mut listing_variables = variables.clone();
+        listing_variables.insert_html("content_list", "");
+        listing_variables.insert_value("content_items", &serde_json::Value::Array(items.clone()));
+        series_items.extend(items);
+
+        let listing_template = super::listing_template(collection);
+        lint.check_template(
+            &listing_template,
+            &templates
+                .load(Path::new(&listing_template))
+                .expect("listing templates missing"),
+            &listing_variables,
+        );
+    }
+
+    if let Some(series_template) = templates.load(Path::new(super::SERIES_TEMPLATE)) {
+        let mut series_variables = variables.clone();
+        series_variables.insert_html("content_list", "");
+        series_variables.insert_value("content_items", &serde_json::Value::Array(series_items));
+        series_variables.insert("series", "");
+        lint.check_template(super::SERIES_TEMPLATE, &series_template, &series_variables);
+    }
+
+    for (name, path) in super::page_templates(config, &collections, &templates) {
+        let template = templates
+            .load(&name)
+            .unwrap_or_else(|| panic!("Could not load template {:?}", path));
+
+        lint.check_template(&utils::to_url_path(&name), &template, &variables);
+    }
+
+    lint
+}
+
+impl TemplateLint {
+    fn check_template(&mut self, name: &str, template: &str, variables: &engine::Variables) {
+        let used = engine::used_variables(template, variables);
+
+        for variable in used.iter().filter(|variable| !variables.contains(variable)) {
+            self.problems
+                .push(format!("{}: unknown variable {{{}}}", name, variable));
+        }
+
+        self.usages.insert(String::from(name), used);
+    }
+
+    /// Checks a template rendered once per file, whose frontmatter provides variables too.
+    fn check_file_template(
+        &mut self,
+        name: &str,
+        template: &str,
+        variables: &engine::Variables,
+        files: &Frontmatters,
+    ) {
+        let used = engine::used_variables(template, variables);
+
+        for variable in used.iter().filter(|variable| !variables.contains(variable)) {
+            let missing: Vec<&PathBuf> = files
+                .iter()
+                .filter(|(_, frontmatter)| !frontmatter.contains_key(variable))
+                .map(|(path, _)| path)
+                .collect();
+
+            if missing.len() == files.len() {
+                self.problems.push(format!(
+                    "{}: unknown variable {{{}}}, no file sets it and it has no default",
+                    name, variable
+                ));
+                continue;
+            }
+
+            for path in missing {
+                self.problems.push(format!(
+                    "{}: missing {{{}}} used by {}, set it in the frontmatter or give it a default",
+                    path.display(),
+                    variable,
+                    name
+                ));
+            }
+        }
+
+        self.usages
+            .entry(String::from(name))
+            .or_default()
+            .extend(used);
+    }
+}
+
+/// Item of `{content_items}` for a file with this frontmatter, its values left empty.
+fn content_item(frontmatter: &BTreeMap<String, String>) -> serde_json::Value {
+    let mut item: serde_json::Map<String, serde_json::Value> = frontmatter
+        .keys()
+        .map(|key| (key.clone(), serde_json::Value::from("")))
+        .collect();
+    for key in ["url", "summary", "word_count", "reading_time"] {
+        item.insert(String::from(key), serde_json::Value::from(""));
+    }
+
+    serde_json::Value::Object(item)
+}
+
+fn template_name(templates: &templates::Templates, path: &Path) -> String {
+    match templates.name_of(path) {
+        Some(name) => utils::to_url_path(&name),
+        None => path.display().to_string(),
+    }
+}
+
+/// Frontmatter of the Markdown files of a directory, and of its subdirectories if `recursive`.
+/// Drafts are left out like in the build, unless `drafts` is set.
+fn read_frontmatters(directory: &Path, recursive: bool, drafts: bool) -> Frontmatters {
+    if !directory.is_dir() {
+        return Vec::new();
+    }
+
+    utils::list_files(directory)
+        .into_iter()
+        .filter(|path| recursive || path.parent() == Some(directory))
+        .filter(|path| utils::is_plaintext_file(path))
+        .map(|path| {
+            let frontmatter = content::read_frontmatter(&path);
+            (path, frontmatter)
+        })
+        .filter(|(_, frontmatter)| drafts || !content::is_draft(frontmatter.get("draft")))
+        .collect()
+}
+
+#[cfg(test)]
+mod test_lint {
+    use super::{read_frontmatters, TemplateLint};
+    use crate::build::engine::Variables;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn it_reports_files_missing_variables_and_unknown_ones() {
+        let mut variables = Variables::new();
+        variables.insert("author", "Anonymous");
+        variables.insert_html("content", "");
+
+        let files = vec![
+            (
+                PathBuf::from("content/a.md"),
+                BTreeMap::from([(String::from("title"), String::from("A"))]),
+            ),
+            (PathBuf::from("content/b.md"), BTreeMap::new()),
+        ];
+
+        let mut lint = TemplateLint {
+            usages: BTreeMap::new(),
+            problems: Vec::new(),
+        };
+        lint.check_file_template(
+            "content.html",
+            "{title} {author} {content} {subtitle}",
+            &variables,
+            &files,
+        );
+
+        assert_eq!(
+            vec![
+                "content.html: unknown variable {subtitle}, no file sets it and it has no default",
+                "content/b.md: missing {title} used by content.html, set it in the frontmatter or give it a default",
+            ],
+            lint.problems
+        );
+        assert_eq!(4, lint.usages["content.html"].len());
+    }
+
+    #[test]
+    fn it_checks_the_variables_used_inside_loops() {
+        let mut variables = Variables::new();
+        variables.insert("site.title", "My site");
+        variables.insert_value(
+            "content_items",
+            &serde_json::Value::Array(vec![super::content_item(&BTreeMap::from([(
+                String::from("title"),
+                String::from("A"),
+            )]))]),
+        );
+
+        let mut lint = TemplateLint {
+            usages: BTreeMap::new(),
+            problems: Vec::new(),
+        };
+        lint.check_template(
+            "blog.html",
+            "{#each content_items}<a href=\"{url}\">{title}</a> {index} {site.title} {site.tagline}{/each}",
+            &variables,
+        );
+
+        assert_eq!(
+            vec!["blog.html: unknown variable {site.tagline}"],
+            lint.problems
+        );
+        assert_eq!(3, lint.usages["blog.html"].len());
+    }
+
+    #[test]
+    fn it_leaves_out_drafts_unless_they_are_built() {
+        let content = tempfile::tempdir().unwrap();
+        std::fs::write(content.path().join("post.md"), "---\ntitle: Post\n---\n").unwrap();
+        std::fs::write(
+            content.path().join("draft.md"),
+            "---\ntitle: Draft\ndraft: true\n---\n",
+        )
+        .unwrap();
+
+        let files = read_frontmatters(content.path(), false, false);
+        assert_eq!(vec![content.path().join("post.md")], paths(&files));
+
+        let files = read_frontmatters(content.path(), false, true);
+        assert_eq!(2, files.len());
+    }
+
+    fn paths(files: &super::Frontmatters) -> Vec<PathBuf> {
+        files.iter().map(|(path, _)| path.clone()).collect()
+    }
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use super::output::BuildOutput;
+
+/// File, in the build directory, listing every file written there by just-html. Files missing
+/// from it were put there by hand and are never removed.
+pub const MANIFEST_FILE: &str = ".jht-manifest.json";
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Manifest {
+    /// Paths relative to the build directory, separated by `/`
+    pub files: BTreeSet<String>,
+}
+
+impl Manifest {
+    pub fn from_output(output: &BuildOutput) -> Self {
+        Manifest {
+            files: output.paths().collect(),
+        }
+    }
+
+    /// Loads the manifest of the build directory, or an empty one if it was never built.
+    pub fn load(build_directory: &Path) -> Self {
+        let manifest_path = build_directory.join(MANIFEST_FILE);
+
+        match std::fs::read_to_string(&manifest_path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                panic!("Failed to parse build manifest {:?}: {}", manifest_path, e)
+            }),
+            Err(_) => Manifest::default(),
+        }
+    }
+
+    pub fn save(&self, build_directory: &Path) {
+        let contents =
+            serde_json::to_string_pretty(self).expect("Failed to serialize build manifest");
+
+        std::fs::write(build_directory.join(MANIFEST_FILE), contents)
+            .expect("Failed to write build manifest");
+    }
+
+    /// Files of this manifest that `current` does not produce anymore.
+    pub fn stale_files<'a>(&'a self, current: &'a Manifest) -> impl Iterator<Item = &'a String> {
+        self.files.difference(&current.files)
+    }
+}
+
+/// Removes the given files from the build directory, along with the directories they leave
+/// empty.
+pub fn remove_files<'a>(build_directory: &Path, files: impl Iterator<Item = &'a String>) {
+    for file in files {
+        let path = build_directory.join(file);
+
+        if !path.exists() {
+            continue;
+        }
+
+        log::info!("Removing {:?}", path);
+        std::fs::remove_file(&path)
+            .unwrap_or_else(|e| panic!("Could not remove {:?}: {}", path, e));
+
+        let mut parent = path.parent();
+        while let Some(directory) = parent {
+            if directory == build_directory || std::fs::remove_dir(directory).is_err() {
+                break;
+            }
+            parent = directory.parent();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_manifest {
+    use super::{remove_files, Manifest};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn it_finds_files_that_are_not_produced_anymore() {
+        let previous = Manifest {
+            files: BTreeSet::from([String::from("index.html"), String::from("blog/old.html")]),
+        };
+        let current = Manifest {
+            files: BTreeSet::from([String::from("index.html"), String::from("blog/new.html")]),
+        };
+
+        assert_eq!(
+            vec!["blog/old.html"],
+            previous.stale_files(&current).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn it_removes_files_and_the_directories_they_leave_empty() {
+        let build_directory = tempfile::tempdir().unwrap();
+        let build_directory = build_directory.path();
+        std::fs::create_dir_all(build_directory.join("blog/2022")).unwrap();
+        std::fs::write(build_directory.join("blog/2022/old.html"), "").unwrap();
+        std::fs::write(build_directory.join("manual.txt"), "").unwrap();
+
+        remove_files(build_directory, [String::from("blog/2022/old.html")].iter());
+
+        assert!(!build_directory.join("blog").exists());
+        assert!(build_directory.join("manual.txt").exists());
+    }
+}
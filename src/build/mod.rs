@@ -1,152 +1,605 @@
+use super::check;
 use super::configuration;
 use super::utils;
 use clap::Parser;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
+use pulldown_cmark::escape::{escape_href, escape_html};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
+mod changes;
 mod content;
+mod data;
+mod engine;
+mod feed;
+mod links;
+pub mod lint;
+pub mod manifest;
+mod navigation;
+mod output;
+mod report;
+mod search;
+mod staging;
+mod templates;
+mod theme;
 
 #[derive(Parser)]
 pub struct BuildCommand {
     #[clap(short, long, global = true)]
     debug: bool,
+
+    /// Remove files produced by previous builds that this build does not produce anymore
+    #[clap(long)]
+    prune: bool,
+
+    /// Run the build without writing anything, listing the files it would change instead. The
+    /// list goes to the standard error when the report goes to the standard output
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Show a unified diff of every modified HTML file in a dry run
+    #[clap(long, requires = "dry-run")]
+    diff: bool,
+
+    /// Write a structured report of the build
+    #[clap(long, arg_enum, value_name = "FORMAT")]
+    report: Option<report::ReportFormat>,
+
+    /// File to write the build report to instead of the standard output
+    #[clap(long, requires = "report", value_name = "PATH")]
+    report_file: Option<PathBuf>,
+
+    /// Check the links between the built pages, reporting broken ones as warnings
+    #[clap(long)]
+    check_links: bool,
 }
 
-pub fn command(_command: &BuildCommand, config: &configuration::Config) {
+pub fn command(command: &BuildCommand, config: &configuration::Config) {
     log::info!("Starting build process");
+    let started = std::time::Instant::now();
+
+    let theme = theme::Theme::load(config);
+    if let Some(theme) = &theme {
+        theme.validate(config);
+    }
+    let templates = templates::Templates::new(config, theme.as_ref());
 
-    create_build_directory(std::path::Path::new(&config.build_config.build_directory));
+    let build_directory = Path::new(&config.build_config.build_directory);
+    let mut output = output::BuildOutput::new();
+    let mut variables = site_variables(config);
+
+    // Build content pages of every collection
+    let collections = config.collections();
+    let mut permalinks = links::Permalinks::new(&config.site.base_url);
+    let mut collection_files: Vec<content::CollectionFiles> = collections
+        .iter()
+        .map(|collection| {
+            content::read_collection(collection, &config.build_config, &mut permalinks)
+        })
+        .collect();
+    let mut markdown_pages =
+        content::read_markdown_pages(Path::new(&config.pages_directory), &config.build_config);
+
+    // Render the Markdown of every page first, backlinks need the links of all of them
+    let mut backlinks = links::Backlinks::new(&config.site.base_url);
+    for files in &mut collection_files {
+        content::render_collection(files, &permalinks, &mut backlinks, &mut output);
+    }
+    content::render_markdown_pages(
+        &mut markdown_pages,
+        Path::new(&config.content_dir),
+        &permalinks,
+        &mut backlinks,
+        &mut output,
+    );
+
+    let mut collection_lists = Vec::new();
+    for (collection, files) in collections.iter().zip(collection_files) {
+        let content_template = templates
+            .load_path(Path::new(&collection.content_template))
+            .expect("Could not load content page template");
+
+        collection_lists.push(content::build_content_pages(
+            collection,
+            files,
+            &content_template,
+            &variables,
+            &config.build_config,
+            &config.site.base_url,
+            &backlinks,
+            &mut output,
+        ));
+    }
+
+    let mut content_list = content::ContentList { items: Vec::new() };
+    for list in &collection_lists {
+        content_list.items.extend(list.items.iter().cloned());
+    }
 
     // Build index.html
-    build_index(
-        &config.templates_directory,
-        &config.build_config.build_directory,
-    )
-    .expect("Could not build index.html");
-
-    // Build content pages
-    let content_list = content::build_content_pages(
-        std::path::Path::new(&config.content_template),
-        std::path::Path::new(&config.build_config.content_directory),
-        std::path::Path::new(&config.content_dir),
+    variables.insert_html(
+        "recent_posts",
+        recent_posts(
+            &content_list,
+            config.build_config.recent_posts,
+            &config.site.base_url,
+        ),
     );
 
-    // Build content list page
-    build_listing_page(
-        content_list,
-        std::path::Path::new(&config.templates_directory),
-        std::path::Path::new(&config.build_config.build_directory),
-        &&config.build_config.content_listing_page,
-    )
-    .expect("Could not build listing page");
+    build_index(&templates, &variables, &mut output).expect("Could not build index.html");
+
+    // Build the listing page and feed of every collection
+    let build_time = chrono::Local::now().to_rfc3339();
+    for (collection, list) in collections.iter().zip(&collection_lists) {
+        build_listing_page(
+            list,
+            &templates,
+            &collection.listing_page,
+            &variables,
+            &config.site.base_url,
+            &mut output,
+        )
+        .expect("Could not build listing page");
+
+        build_series_pages(
+            collection,
+            list,
+            &templates,
+            &variables,
+            &config.site.base_url,
+            &mut output,
+        );
+
+        feed::build_feed(collection, list, &config.site, &build_time, &mut output);
+    }
+
+    if config.search.enabled {
+        search::build_search_index(
+            &content_list,
+            &config.search,
+            &config.site.base_url,
+            &mut output,
+        );
+    }
 
     // Build other pages
-    build_stylesheets(
-        std::path::Path::new(&config.templates_directory),
-        std::path::Path::new(&config.build_config.build_directory),
+    build_template_pages(
+        &templates,
+        &page_templates(config, &collections, &templates),
+        &variables,
+        &mut output,
     );
-}
 
-fn create_build_directory(build_directory_path: &Path) {
-    match build_directory_path.exists() {
-        true => {}
-        false => {
-            log::debug!("Creating empty build directory");
-            std::fs::create_dir_all(build_directory_path)
-                .expect("Failed to create build directory");
+    let page_template = templates
+        .load(Path::new(PAGE_TEMPLATE))
+        .or_else(|| templates.load_path(Path::new(&config.content_template)))
+        .expect("Could not load content page template");
+    content::build_markdown_pages(
+        markdown_pages,
+        &page_template,
+        &variables,
+        &config.build_config,
+        &mut output,
+    );
+
+    build_stylesheets(&templates, &mut output);
+
+    build_assets(&templates, &mut output);
+
+    if command.check_links {
+        check_links(&mut output, build_directory, &config.site.base_url);
+    }
+
+    match command.dry_run {
+        true => {
+            // Keep the standard output to the report alone, e.g. to pipe it into `jq`
+            let report_to_stdout = command.report.is_some() && command.report_file.is_none();
+            let mut changes_output: Box<dyn Write> = match report_to_stdout {
+                true => Box::new(std::io::stderr()),
+                false => Box::new(std::io::stdout()),
+            };
+
+            report_changes(
+                &output,
+                build_directory,
+                command.prune,
+                command.diff,
+                &mut changes_output,
+            )
         }
+        false => write_output(&output, build_directory, command.prune),
+    }
+
+    if let Some(format) = command.report {
+        report::BuildReport::new(
+            &output,
+            &content_list,
+            build_directory,
+            &config.site.base_url,
+            command.dry_run,
+            started.elapsed(),
+        )
+        .write(format, command.report_file.as_deref());
     }
 }
 
-fn build_index(templates_directory: &String, build_directory: &String) -> Result<(), ()> {
+fn report_changes(
+    output: &output::BuildOutput,
+    build_directory: &Path,
+    prune: bool,
+    with_diffs: bool,
+    changes_output: &mut dyn Write,
+) {
+    let changes = changes::compare(output, build_directory, prune, with_diffs);
+
     log::info!(
-        "Creating index.html from {}/index.html",
-        templates_directory
+        "Dry run: {} file(s) would change in {:?}",
+        changes.len(),
+        build_directory
     );
 
-    let x = format!("{}/index.html", templates_directory);
-    let index_template =
-        std::fs::read_to_string(std::path::Path::new(&x)).expect("index.html template missing");
+    for change in &changes {
+        writeln!(changes_output, "{:<8} {}", change.change, change.path)
+            .expect("Could not write the changes");
+    }
+
+    for diff in changes.iter().filter_map(|change| change.diff.as_ref()) {
+        write!(changes_output, "\n{}", diff).expect("Could not write the changes");
+    }
+}
+
+/// Checks the links of the output before it is written, against the output and the files the
+/// build directory already holds outside of it.
+fn check_links(output: &mut output::BuildOutput, build_directory: &Path, base_url: &str) {
+    log::info!("Checking links");
+
+    let mut site = check::links::Site::new();
+
+    for previous_file in utils::list_files(build_directory) {
+        let name = utils::to_url_path(
+            previous_file
+                .strip_prefix(build_directory)
+                .expect("Listed files are inside their directory"),
+        );
+        site.add_file(name);
+    }
 
-    let y = format!("{}/index.html", build_directory);
-    let mut new_index_page =
-        File::create(std::path::Path::new(&y)).expect("Failed creating index in build directory");
+    for (path, file) in output.files() {
+        let name = utils::to_url_path(path);
+        match file {
+            output::OutputFile::Rendered { contents, .. } if check::links::is_html(&name) => {
+                site.add_page(name, contents.clone())
+            }
+            _ => site.add_file(name),
+        }
+    }
+
+    for broken_link in site.broken_links(base_url) {
+        output.warn(broken_link.to_string());
+    }
+}
+
+/// Writes the output and the build manifest to a staging directory and swaps it into place
+/// once complete, so a failed build never leaves a half-updated site behind.
+///
+/// Files of the previous build that this build does not produce are carried over, except for
+/// the stale built files when `prune` is set.
+fn write_output(output: &output::BuildOutput, build_directory: &Path, prune: bool) {
+    let previous_manifest = manifest::Manifest::load(build_directory);
+    let mut current_manifest = manifest::Manifest::from_output(output);
+
+    let stale_files: BTreeSet<String> = previous_manifest
+        .stale_files(&current_manifest)
+        .cloned()
+        .collect();
+
+    let staging_directory = staging::StagingDirectory::create(build_directory);
+
+    for previous_file in utils::list_files(build_directory) {
+        let name = utils::to_url_path(
+            previous_file
+                .strip_prefix(build_directory)
+                .expect("Listed files are inside their directory"),
+        );
+
+        if name == manifest::MANIFEST_FILE || current_manifest.files.contains(&name) {
+            continue;
+        }
+
+        if stale_files.contains(&name) {
+            if prune {
+                log::info!("Removing stale file {:?}", name);
+                continue;
+            }
+
+            // Keep tracking stale files so a later prune or clean still removes them
+            log::debug!("Keeping stale file {:?}", name);
+            current_manifest.files.insert(name.clone());
+        }
+
+        staging_directory.carry_over(&previous_file, &name);
+    }
 
-    new_index_page
-        .write_all(index_template.as_bytes())
-        .expect("Failed writing to build/index.html");
+    output.write(staging_directory.path());
+    current_manifest.save(staging_directory.path());
+
+    staging_directory.commit();
+}
+
+/// Template of the standalone Markdown pages, relative to the templates directories.
+const PAGE_TEMPLATE: &str = "page.html";
+
+/// Template of the index pages of series, relative to the templates directories.
+const SERIES_TEMPLATE: &str = "series.html";
+
+/// Values every template can use, as `{name}`.
+fn site_variables(config: &configuration::Config) -> engine::Variables {
+    let mut variables = engine::Variables::new();
+    let now = chrono::Local::now();
+
+    // Defaults first, so they never hide the values below
+    for (name, value) in &config.defaults {
+        variables.insert(name.as_str(), value.as_str());
+    }
+
+    variables.insert_value(
+        "site",
+        &serde_json::to_value(&config.site).expect("Failed to serialize site configuration"),
+    );
+    variables.insert_value(
+        "params",
+        &serde_json::to_value(&config.params).expect("Failed to serialize params"),
+    );
+    variables.insert_value("data", &data::load_data(Path::new(&config.data_directory)));
+    variables.insert("build_time", now.to_rfc3339());
+    variables.insert("year", now.format("%Y").to_string());
+    variables.insert("version", env!("CARGO_PKG_VERSION"));
+    variables.insert(
+        "search_index_url",
+        search::index_url(&config.search, &config.site.base_url),
+    );
+
+    variables
+}
+
+/// Lists links to the most recent content pages, by `date` in their frontmatter.
+fn recent_posts(content_list: &content::ContentList, count: usize, base_url: &str) -> String {
+    let mut items: Vec<&content::ContentItem> = content_list.items.iter().collect();
+    items.sort_by(|a, b| b.frontmatter.get("date").cmp(&a.frontmatter.get("date")));
+
+    let mut list = String::from("<ul class=\"recent-posts\">");
+    for item in items.into_iter().take(count) {
+        list.push_str("<li><a href=\"");
+        escape_href(&mut list, &utils::join_url(base_url, &item.href))
+            .expect("Writing to a string cannot fail");
+        list.push_str("\">");
+        escape_html(&mut list, item.title().unwrap_or(&item.href))
+            .expect("Writing to a string cannot fail");
+        list.push_str("</a></li>");
+    }
+    list.push_str("</ul>");
+
+    list
+}
+
+fn build_index(
+    templates: &templates::Templates,
+    variables: &engine::Variables,
+    output: &mut output::BuildOutput,
+) -> Result<(), ()> {
+    log::info!("Creating index.html from the index.html template");
+
+    let index_path = templates
+        .find(Path::new("index.html"))
+        .expect("index.html template missing");
+    let index_template = templates
+        .load(Path::new("index.html"))
+        .expect("index.html template missing");
+
+    let index_page =
+        engine::render_page(Path::new("index.html"), &index_template, variables, output);
+
+    output.render("index.html", index_page, Some(&index_path));
     Ok(())
 }
 
+/// Builds the listing page of a collection, where `{content_list}` links to every page with its
+/// summary and `{content_items}` holds their frontmatter, `url`, `summary`, `word_count` and
+/// `reading_time` for `{#each content_items}` loops.
 fn build_listing_page(
-    content_list: content::ContentList,
-    templates_directory: &Path,
-    build_directory: &Path,
-    content_listing_page_name: &String,
+    content_list: &content::ContentList,
+    templates: &templates::Templates,
+    content_listing_page_name: &str,
+    variables: &engine::Variables,
+    base_url: &str,
+    output: &mut output::BuildOutput,
 ) -> Result<(), ()> {
-    log::info!("Adding {:?} to listing page", content_list.items);
-
     let mut content_hrefs = String::new();
+    let mut content_items = Vec::new();
 
     // TODO: The HTML from this should come from a template.
-    for content in content_list.items {
-        content_hrefs.push_str(&format!(
-            "<a href={}>{}</a> <br />",
-            // TODO Change this to strip based on build_directory filepath
-            content.strip_prefix("./build/").expect("big bad"),
-            content.strip_prefix("./build/").expect("big bad")
-        ));
+    for content in &content_list.items {
+        log::info!("Adding {:?} to listing page", content.href);
+        content_hrefs.push_str("<a href=\"");
+        escape_href(&mut content_hrefs, &content.href).expect("Writing to a string cannot fail");
+        content_hrefs.push_str("\">");
+        escape_html(&mut content_hrefs, content.title().unwrap_or(&content.href))
+            .expect("Writing to a string cannot fail");
+        content_hrefs.push_str("</a> <br />");
+
+        if !content.summary.is_empty() {
+            content_hrefs.push_str("<p class=\"summary\">");
+            escape_html(&mut content_hrefs, &content.summary)
+                .expect("Writing to a string cannot fail");
+            content_hrefs.push_str("</p>");
+        }
+
+        let mut item =
+            serde_json::to_value(&content.frontmatter).expect("Failed to serialize frontmatter");
+        item["url"] = serde_json::Value::from(utils::join_url(base_url, &content.href));
+        item["summary"] = serde_json::Value::from(content.summary.as_str());
+        item["word_count"] = serde_json::Value::from(content.word_count);
+        item["reading_time"] = serde_json::Value::from(content.reading_time);
+        content_items.push(item);
     }
 
     let mut z = String::from(content_listing_page_name);
     z.push_str(".html");
-    let mut x = templates_directory.clone().to_path_buf();
 
-    x.push(std::path::Path::new(&z));
+    let list_path = templates
+        .find(Path::new(&z))
+        .expect("listing templates missing");
+    let list_template = templates
+        .load(Path::new(&z))
+        .expect("listing templates missing");
 
-    let list_template = std::fs::read_to_string(&x).expect("listing templates missing");
+    let mut variables = variables.clone();
+    variables.insert_html("content_list", content_hrefs);
+    variables.insert_value("content_items", &serde_json::Value::Array(content_items));
 
-    let list_page = list_template.replace("{content_list}", &content_hrefs);
+    let list_page = engine::render_page(Path::new(&z), &list_template, &variables, output);
 
-    let mut y = build_directory.clone().to_path_buf();
+    output.render(z, list_page, Some(&list_path));
 
-    y.push(std::path::Path::new(&z));
-    let mut new_content_list = File::create(y).expect("unable to create new listing page");
+    Ok(())
+}
 
-    new_content_list
-        .write_all(list_page.as_bytes())
-        .expect("could not create new listing file");
+/// Builds an index page for every series of a collection, with the `series.html` template or
+/// the collection's listing template otherwise, where `{series}` names the series and
+/// `{content_list}` lists its parts.
+fn build_series_pages(
+    collection: &configuration::CollectionConfig,
+    content_list: &content::ContentList,
+    templates: &templates::Templates,
+    variables: &engine::Variables,
+    base_url: &str,
+    output: &mut output::BuildOutput,
+) {
+    let mut series: BTreeMap<&str, Vec<&content::ContentItem>> = BTreeMap::new();
+    for item in &content_list.items {
+        if let Some(name) = item.series() {
+            series.entry(name).or_default().push(item);
+        }
+    }
 
-    Ok(())
+    if series.is_empty() {
+        return;
+    }
+
+    let template_name = match templates.find(Path::new(SERIES_TEMPLATE)) {
+        Some(_) => String::from(SERIES_TEMPLATE),
+        None => listing_template(collection),
+    };
+    let template_path = templates
+        .find(Path::new(&template_name))
+        .expect("listing templates missing");
+    let template = templates
+        .load(Path::new(&template_name))
+        .expect("listing templates missing");
+
+    for (name, mut items) in series {
+        log::info!("Building index of series {:?}", name);
+
+        items.sort_by_cached_key(|item| navigation::series_part_key(&item.frontmatter, &item.href));
+        let parts: Vec<navigation::NavigationPage> = items
+            .into_iter()
+            .map(|item| navigation::NavigationPage {
+                url: utils::join_url(base_url, &item.href),
+                title: String::from(item.title().unwrap_or(&item.href)),
+            })
+            .collect();
+
+        let path = navigation::series_index_path(Path::new(&collection.output_directory), name);
+
+        let mut variables = variables.clone();
+        variables.insert("series", name);
+        variables.insert_html(
+            "content_list",
+            navigation::series_list_html(&parts.iter().collect::<Vec<_>>(), None),
+        );
+
+        let page = engine::render_page(&path, &template, &variables, output);
+        output.render(path, page, Some(&template_path));
+    }
+}
+
+/// Every HTML template that is not a partial, nor rendered separately, e.g. an `about.html`
+/// template, by name and path.
+fn page_templates(
+    config: &configuration::Config,
+    collections: &[configuration::CollectionConfig],
+    templates: &templates::Templates,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut rendered_separately = vec![
+        PathBuf::from("index.html"),
+        PathBuf::from(PAGE_TEMPLATE),
+        PathBuf::from(SERIES_TEMPLATE),
+    ];
+    rendered_separately.extend(templates.name_of(Path::new(&config.content_template)));
+    for collection in collections {
+        rendered_separately.push(PathBuf::from(listing_template(collection)));
+        rendered_separately.extend(templates.name_of(Path::new(&collection.content_template)));
+    }
+
+    templates
+        .files()
+        .into_iter()
+        .filter(|(name, _)| {
+            name.extension() == Some("html".as_ref())
+                && !name.starts_with(templates::PARTIALS_DIRECTORY)
+                && !name.starts_with(templates::ASSETS_DIRECTORY)
+                && !rendered_separately.contains(name)
+        })
+        .collect()
 }
 
-fn build_stylesheets(templates_directory: &Path, build_directory: &Path) {
+fn listing_template(collection: &configuration::CollectionConfig) -> String {
+    format!("{}.html", collection.listing_page)
+}
+
+/// Renders each of the page templates into a page at the same path.
+fn build_template_pages(
+    templates: &templates::Templates,
+    pages: &[(PathBuf, PathBuf)],
+    variables: &engine::Variables,
+    output: &mut output::BuildOutput,
+) {
+    for (name, template_file) in pages {
+        log::info!("Building page {:?}", name);
+
+        let template = templates
+            .load(name)
+            .unwrap_or_else(|| panic!("Could not load template {:?}", template_file));
+
+        let page = engine::render_page(name, &template, variables, output);
+
+        output.render(name, page, Some(template_file));
+    }
+}
+
+fn build_stylesheets(templates: &templates::Templates, output: &mut output::BuildOutput) {
     log::info!("Building stylesheets");
 
-    let all_templates = std::fs::read_dir(templates_directory)
-        .expect("Failed reading templates in templates directory");
+    let stylesheets = templates
+        .files()
+        .into_iter()
+        .filter(|(name, _)| name.parent() == Some(Path::new("")) && utils::is_stylesheet(name));
 
-    let stylesheets = all_templates.filter(|x| {
-        if let Ok(template_path) = x {
-            utils::is_stylesheet(&template_path.path())
-        } else {
-            false
-        }
-    });
-
-    for stylesheet in stylesheets {
-        match stylesheet {
-            Ok(template_file) => {
-                log::debug!("Building {:?}", template_file.path());
-                // Maybe do some minimization here
-                let mut built_file = build_directory.to_path_buf();
-                built_file.push(template_file.file_name());
-
-                std::fs::copy(template_file.path(), built_file)
-                    .expect("Could not copy stylesheet to build directory");
-            }
-            Err(_e) => {}
-        }
+    for (name, template_file) in stylesheets {
+        log::debug!("Building {:?}", template_file);
+        // Maybe do some minimization here
+        output.copy(name, &template_file);
+    }
+}
+
+fn build_assets(templates: &templates::Templates, output: &mut output::BuildOutput) {
+    let assets = templates
+        .files()
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(templates::ASSETS_DIRECTORY));
+
+    for (name, asset_file) in assets {
+        log::debug!("Copying asset {:?}", asset_file);
+        output.copy(name, &asset_file);
     }
 }
@@ -0,0 +1,133 @@
+use pulldown_cmark::escape::{escape_href, escape_html};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::utils;
+
+/// A page of a collection, as linked to from its neighbours.
+pub struct NavigationPage {
+    pub url: String,
+    pub title: String,
+}
+
+/// Links a content page to the pages around it: its neighbours in the collection's order and
+/// the other parts of its series. Every link is empty when there is none.
+#[derive(Default)]
+pub struct Navigation {
+    pub backlinks: String,
+    pub prev: String,
+    pub next: String,
+    pub series: String,
+}
+
+/// Path of the index page of a series, relative to the build directory, e.g.
+/// `blog/series/learning-rust.html`.
+pub fn series_index_path(collection_directory: &Path, series: &str) -> PathBuf {
+    collection_directory
+        .join("series")
+        .join(format!("{}.html", utils::slugify(series)))
+}
+
+/// Orders the parts of a series by their `series_part` frontmatter number, then by `date`,
+/// oldest first, whatever the order of their collection, and finally by `name`.
+pub fn series_part_key(
+    frontmatter: &BTreeMap<String, String>,
+    name: &str,
+) -> (usize, Option<String>, String) {
+    (
+        frontmatter
+            .get("series_part")
+            .and_then(|part| part.trim().parse().ok())
+            .unwrap_or(usize::MAX),
+        frontmatter.get("date").cloned(),
+        String::from(name),
+    )
+}
+
+/// Link to a page, e.g. `<a class="prev" href="...">Title</a>`.
+pub fn link_html(page: &NavigationPage, class: &str) -> String {
+    let mut link = format!("<a class=\"{}\" href=\"", class);
+    escape_href(&mut link, &page.url).expect("Writing to a string cannot fail");
+    link.push_str("\">");
+    escape_html(&mut link, &page.title).expect("Writing to a string cannot fail");
+    link.push_str("</a>");
+
+    link
+}
+
+/// Lists the parts of a series in order, linking to every part but the `current` one.
+pub fn series_list_html(parts: &[&NavigationPage], current: Option<usize>) -> String {
+    let mut list = String::from("<ol class=\"series-parts\">");
+
+    for (index, part) in parts.iter().enumerate() {
+        if Some(index) == current {
+            list.push_str("<li class=\"current\">");
+            escape_html(&mut list, &part.title).expect("Writing to a string cannot fail");
+        } else {
+            list.push_str("<li>");
+            list.push_str(&link_html(part, "series-part"));
+        }
+        list.push_str("</li>");
+    }
+
+    list.push_str("</ol>");
+    list
+}
+
+/// Navigation block of a part of a series, linking to the series index and to every part.
+pub fn series_html(
+    series: &str,
+    index_url: &str,
+    parts: &[&NavigationPage],
+    current: usize,
+) -> String {
+    let mut navigation = String::from("<nav class=\"series\"><p>");
+    navigation.push_str(&link_html(
+        &NavigationPage {
+            url: String::from(index_url),
+            title: String::from(series),
+        },
+        "series-index",
+    ));
+    navigation.push_str(&format!(", part {} of {}</p>", current + 1, parts.len()));
+    navigation.push_str(&series_list_html(parts, Some(current)));
+    navigation.push_str("</nav>");
+
+    navigation
+}
+
+#[cfg(test)]
+mod test_navigation {
+    use super::{series_html, series_index_path, NavigationPage};
+    use std::path::Path;
+
+    #[test]
+    fn it_links_every_part_of_a_series_but_the_current_one() {
+        let parts = [
+            NavigationPage {
+                url: String::from("/blog/one.html"),
+                title: String::from("Setup"),
+            },
+            NavigationPage {
+                url: String::from("/blog/two.html"),
+                title: String::from("Types & traits"),
+            },
+        ];
+
+        assert_eq!(
+            "<nav class=\"series\"><p><a class=\"series-index\" href=\"/blog/series/rust.html\">Rust</a>, part 2 of 2</p>\
+             <ol class=\"series-parts\"><li><a class=\"series-part\" href=\"/blog/one.html\">Setup</a></li>\
+             <li class=\"current\">Types &amp; traits</li></ol></nav>",
+            series_html(
+                "Rust",
+                "/blog/series/rust.html",
+                &parts.iter().collect::<Vec<_>>(),
+                1
+            )
+        );
+        assert_eq!(
+            Path::new("blog/series/learning-rust.html"),
+            series_index_path(Path::new("blog"), "Learning Rust")
+        );
+    }
+}
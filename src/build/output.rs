@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::utils;
+
+pub enum OutputFile {
+    /// Page rendered by the build, from the template or content file at `source` if known
+    Rendered {
+        contents: String,
+        source: Option<PathBuf>,
+    },
+    /// File copied as is from the given path, e.g. stylesheets and assets
+    Copied(PathBuf),
+}
+
+impl OutputFile {
+    pub fn read(&self) -> Vec<u8> {
+        match self {
+            OutputFile::Rendered { contents, .. } => contents.as_bytes().to_vec(),
+            OutputFile::Copied(source) => std::fs::read(source)
+                .unwrap_or_else(|e| panic!("Could not read {:?}: {}", source, e)),
+        }
+    }
+
+    /// File the output was produced from.
+    pub fn source(&self) -> Option<&Path> {
+        match self {
+            OutputFile::Rendered { source, .. } => source.as_deref(),
+            OutputFile::Copied(source) => Some(source),
+        }
+    }
+}
+
+/// Every file produced by a build, by path relative to the build directory. Nothing touches
+/// the build directory until the output is written.
+#[derive(Default)]
+pub struct BuildOutput {
+    files: BTreeMap<PathBuf, OutputFile>,
+    warnings: Vec<String>,
+}
+
+impl BuildOutput {
+    pub fn new() -> Self {
+        BuildOutput::default()
+    }
+
+    /// Adds a rendered page, produced from the template or content file at `source`.
+    pub fn render(&mut self, path: impl Into<PathBuf>, contents: String, source: Option<&Path>) {
+        self.add(
+            path.into(),
+            OutputFile::Rendered {
+                contents,
+                source: source.map(Path::to_path_buf),
+            },
+        );
+    }
+
+    pub fn copy(&mut self, path: impl Into<PathBuf>, source: &Path) {
+        self.add(path.into(), OutputFile::Copied(source.to_path_buf()));
+    }
+
+    fn add(&mut self, path: PathBuf, file: OutputFile) {
+        if self.files.insert(path.clone(), file).is_some() {
+            self.warn(format!(
+                "{:?} is produced more than once, keeping the last one",
+                path
+            ));
+        }
+    }
+
+    /// Logs a warning and keeps it for the build report.
+    pub fn warn(&mut self, message: String) {
+        log::warn!("{}", message);
+        self.warnings.push(message);
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (&PathBuf, &OutputFile)> {
+        self.files.iter()
+    }
+
+    /// Paths of every output file, relative to the build directory and separated by `/`.
+    pub fn paths(&self) -> impl Iterator<Item = String> + '_ {
+        self.files.keys().map(|path| utils::to_url_path(path))
+    }
+
+    pub fn write(&self, build_directory: &Path) {
+        for (path, file) in &self.files {
+            let destination = build_directory.join(path);
+
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent)
+                    .unwrap_or_else(|e| panic!("Could not create {:?}: {}", parent, e));
+            }
+
+            log::debug!("Writing {:?}", destination);
+
+            match file {
+                OutputFile::Rendered { contents, .. } => std::fs::write(&destination, contents)
+                    .unwrap_or_else(|e| panic!("Could not write {:?}: {}", destination, e)),
+                OutputFile::Copied(source) => {
+                    std::fs::copy(source, &destination).unwrap_or_else(|e| {
+                        panic!("Could not copy {:?} to {:?}: {}", source, destination, e)
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use super::content::ContentList;
+use super::output::BuildOutput;
+use crate::utils;
+
+#[derive(clap::ArgEnum, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    Json,
+}
+
+/// Structured summary of a build, for scripts and dashboards.
+#[derive(Serialize, Debug)]
+pub struct BuildReport {
+    pub build_directory: String,
+    pub dry_run: bool,
+    pub duration_ms: f64,
+    pub files: Vec<ReportedFile>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReportedFile {
+    /// Path relative to the build directory
+    pub output: String,
+    pub url: String,
+    /// Template, content file or asset the file was produced from
+    pub input: Option<String>,
+    #[serde(flatten)]
+    pub content: Option<ReportedContent>,
+}
+
+/// Details only known for pages built from content files.
+#[derive(Serialize, Debug)]
+pub struct ReportedContent {
+    pub frontmatter: BTreeMap<String, String>,
+    pub word_count: usize,
+    pub reading_time_minutes: usize,
+    pub render_time_ms: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unresolved_wiki_links: Vec<String>,
+}
+
+impl BuildReport {
+    pub fn new(
+        output: &BuildOutput,
+        content_list: &ContentList,
+        build_directory: &Path,
+        base_url: &str,
+        dry_run: bool,
+        duration: Duration,
+    ) -> Self {
+        let content_by_href: BTreeMap<&str, _> = content_list
+            .items
+            .iter()
+            .map(|item| (item.href.as_str(), item))
+            .collect();
+
+        let files = output
+            .files()
+            .map(|(path, file)| {
+                let output_path = utils::to_url_path(path);
+
+                let content =
+                    content_by_href
+                        .get(output_path.as_str())
+                        .map(|item| ReportedContent {
+                            frontmatter: item.frontmatter.clone(),
+                            word_count: item.word_count,
+                            reading_time_minutes: item.reading_time,
+                            render_time_ms: as_milliseconds(item.render_time),
+                            unresolved_wiki_links: item.unresolved_wiki_links.clone(),
+                        });
+
+                ReportedFile {
+                    url: utils::join_url(base_url, &output_path),
+                    input: file
+                        .source()
+                        .map(|source| source.to_string_lossy().to_string()),
+                    output: output_path,
+                    content,
+                }
+            })
+            .collect();
+
+        BuildReport {
+            build_directory: build_directory.to_string_lossy().to_string(),
+            dry_run,
+            duration_ms: as_milliseconds(duration),
+            files,
+            warnings: output.warnings().to_vec(),
+        }
+    }
+
+    /// Writes the report to `destination`, or to the standard output if there is none.
+    pub fn write(&self, format: ReportFormat, destination: Option<&Path>) {
+        let contents = match format {
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(self).expect("Failed to serialize build report")
+            }
+        };
+
+        match destination {
+            Some(path) => {
+                std::fs::write(path, contents)
+                    .unwrap_or_else(|e| panic!("Could not write build report {:?}: {}", path, e));
+                log::info!("Wrote build report to {:?}", path);
+            }
+            None => println!("{}", contents),
+        }
+    }
+}
+
+fn as_milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
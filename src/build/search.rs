@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use super::content::{ContentItem, ContentList};
+use super::output::BuildOutput;
+use crate::configuration::{SearchConfig, SearchField};
+use crate::utils;
+
+/// Search index of the content pages, loaded by the browser for client-side search.
+#[derive(Serialize, Debug)]
+struct SearchIndex {
+    /// Fields indexed for every page
+    fields: Vec<SearchField>,
+    pages: Vec<IndexedPage>,
+}
+
+/// A content page in the search index. The URL, word count and reading time are always there to
+/// display results.
+#[derive(Serialize, Debug)]
+struct IndexedPage {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    word_count: usize,
+    /// Estimated reading time, in minutes
+    reading_time: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headings: Option<Vec<String>>,
+    /// Distinct lowercase words of the page, without stop words, separated by spaces
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+/// Adds the search index of the content pages to the output.
+pub fn build_search_index(
+    content_list: &ContentList,
+    search: &SearchConfig,
+    base_url: &str,
+    output: &mut BuildOutput,
+) {
+    log::info!("Building search index {:?}", search.index_file);
+
+    let index = SearchIndex {
+        fields: search.fields.clone(),
+        pages: content_list
+            .items
+            .iter()
+            .map(|item| index_page(item, search, base_url))
+            .collect(),
+    };
+
+    output.render(
+        &search.index_file,
+        serde_json::to_string(&index).expect("Failed to serialize search index"),
+        None,
+    );
+}
+
+/// URL of the search index, for templates to load it from.
+pub fn index_url(search: &SearchConfig, base_url: &str) -> String {
+    match search.enabled {
+        true => utils::join_url(base_url, &search.index_file),
+        false => String::new(),
+    }
+}
+
+fn index_page(item: &ContentItem, search: &SearchConfig, base_url: &str) -> IndexedPage {
+    let indexes = |field| search.fields.contains(&field);
+
+    IndexedPage {
+        url: utils::join_url(base_url, &item.href),
+        title: indexes(SearchField::Title)
+            .then(|| String::from(item.title().unwrap_or(&item.href))),
+        word_count: item.word_count,
+        reading_time: item.reading_time,
+        tags: indexes(SearchField::Tags).then(|| item.tags()),
+        headings: indexes(SearchField::Headings).then(|| item.headings.clone()),
+        body: indexes(SearchField::Body).then(|| terms(&item.text, &search.stop_words)),
+    }
+}
+
+/// Distinct lowercase words of a text, without stop words, separated by spaces.
+fn terms(text: &str, stop_words: &[String]) -> String {
+    let stop_words: BTreeSet<String> = stop_words.iter().map(|word| word.to_lowercase()).collect();
+
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| word.chars().count() > 1 && !stop_words.contains(word))
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test_search {
+    use super::{index_page, terms};
+    use crate::build::content::ContentItem;
+    use crate::configuration::{SearchConfig, SearchField};
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    #[test]
+    fn it_indexes_distinct_words_without_stop_words() {
+        let stop_words = vec![String::from("the"), String::from("is")];
+
+        assert_eq!(
+            "build built fast site static",
+            terms(
+                "The static site is built... the BUILD is fast: a site!",
+                &stop_words
+            )
+        );
+    }
+
+    #[test]
+    fn it_indexes_only_the_configured_fields() {
+        let item = ContentItem {
+            href: String::from("blog/post.html"),
+            frontmatter: BTreeMap::from([
+                (String::from("title"), String::from("Post")),
+                (String::from("tags"), String::from("rust, web")),
+            ]),
+            word_count: 2,
+            reading_time: 1,
+            render_time: Duration::ZERO,
+            unresolved_wiki_links: Vec::new(),
+            headings: Vec::new(),
+            text: String::from("Static sites"),
+            summary: String::new(),
+        };
+        let search = SearchConfig {
+            fields: vec![SearchField::Body],
+            ..SearchConfig::default()
+        };
+
+        let page = index_page(&item, &search, "/");
+
+        assert_eq!(None, page.title);
+        assert_eq!(None, page.tags);
+        assert_eq!(Some(String::from("sites static")), page.body);
+    }
+}
@@ -0,0 +1,243 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// A sibling of the build directory that a build is written to, swapped into place only once
+/// the whole build succeeded. Dropping it without committing removes it and leaves the build
+/// directory untouched.
+pub struct StagingDirectory {
+    build_directory: PathBuf,
+    path: PathBuf,
+    committed: bool,
+}
+
+impl StagingDirectory {
+    pub fn create(build_directory: &Path) -> Self {
+        let path = sibling(build_directory, "jht-staging");
+
+        if path.exists() {
+            log::debug!("Removing leftover staging directory {:?}", path);
+            std::fs::remove_dir_all(&path)
+                .unwrap_or_else(|e| panic!("Could not remove {:?}: {}", path, e));
+        }
+
+        std::fs::create_dir_all(&path)
+            .unwrap_or_else(|e| panic!("Could not create staging directory {:?}: {}", path, e));
+
+        StagingDirectory {
+            build_directory: build_directory.to_path_buf(),
+            path,
+            committed: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds a file of the previous build to the staging directory as `name`, hard linked
+    /// rather than copied when the file system allows it.
+    pub fn carry_over(&self, previous_file: &Path, name: &str) {
+        let destination = self.path.join(name);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent).expect("Failed to create staging directory");
+        }
+
+        if std::fs::hard_link(previous_file, &destination).is_ok() {
+            return;
+        }
+
+        std::fs::copy(previous_file, &destination).unwrap_or_else(|e| {
+            panic!(
+                "Could not carry {:?} over to the new build: {}",
+                previous_file, e
+            )
+        });
+    }
+
+    /// Replaces the build directory with the staging directory in a single step, so the build
+    /// directory always exists for a running `serve`.
+    pub fn commit(mut self) {
+        if !self.build_directory.exists() {
+            std::fs::rename(&self.path, &self.build_directory).unwrap_or_else(|e| {
+                panic!(
+                    "Could not move the build into {:?}: {}",
+                    self.build_directory, e
+                )
+            });
+            self.committed = true;
+            return;
+        }
+
+        match exchange(&self.path, &self.build_directory) {
+            Ok(()) => {}
+            Err(e) => {
+                log::debug!("Could not swap the directories in one step: {}", e);
+                self.replace_in_two_steps();
+            }
+        }
+
+        self.committed = true;
+        log::debug!("Swapped {:?} into place", self.build_directory);
+
+        // The staging directory now holds the previous build
+        if self.path.exists() {
+            std::fs::remove_dir_all(&self.path)
+                .unwrap_or_else(|e| log::warn!("Could not remove {:?}: {}", self.path, e));
+        }
+    }
+
+    /// Moves the build directory out of the way before moving the staging directory in, for
+    /// the platforms and file systems without an atomic exchange.
+    fn replace_in_two_steps(&self) {
+        let previous = sibling(&self.build_directory, "jht-previous");
+
+        if previous.exists() {
+            std::fs::remove_dir_all(&previous)
+                .unwrap_or_else(|e| panic!("Could not remove {:?}: {}", previous, e));
+        }
+
+        std::fs::rename(&self.build_directory, &previous).unwrap_or_else(|e| {
+            panic!(
+                "Could not move {:?} out of the way: {}",
+                self.build_directory, e
+            )
+        });
+
+        if let Err(e) = std::fs::rename(&self.path, &self.build_directory) {
+            std::fs::rename(&previous, &self.build_directory)
+                .expect("Could not restore the previous build");
+            panic!(
+                "Could not move the build into {:?}: {}",
+                self.build_directory, e
+            );
+        }
+
+        // Removed along with the staging directory
+        std::fs::rename(&previous, &self.path)
+            .unwrap_or_else(|e| log::warn!("Could not move {:?}: {}", previous, e));
+    }
+}
+
+impl Drop for StagingDirectory {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+
+        log::warn!(
+            "Build did not complete, keeping the previous output in {:?}",
+            self.build_directory
+        );
+
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            log::warn!("Could not remove staging directory {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Atomically exchanges two directories with `renameat2(RENAME_EXCHANGE)`.
+#[cfg(target_os = "linux")]
+fn exchange(a: &Path, b: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let to_c_string = |path: &Path| {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+    };
+    let (a, b) = (to_c_string(a)?, to_c_string(b)?);
+
+    // SAFETY: both paths are valid NUL terminated strings that outlive the call
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            a.as_ptr(),
+            libc::AT_FDCWD,
+            b.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+
+    match result {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn exchange(_a: &Path, _b: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "atomic exchange is only supported on Linux",
+    ))
+}
+
+/// `.<build directory name>.<suffix>` next to the build directory.
+fn sibling(build_directory: &Path, suffix: &str) -> PathBuf {
+    let name = build_directory
+        .file_name()
+        .expect("The build directory must have a name");
+
+    let mut sibling_name = OsString::from(".");
+    sibling_name.push(name);
+    sibling_name.push(".");
+    sibling_name.push(suffix);
+
+    build_directory.with_file_name(sibling_name)
+}
+
+#[cfg(test)]
+mod test_staging {
+    use super::StagingDirectory;
+
+    #[test]
+    fn it_swaps_the_staging_directory_into_place_on_commit() {
+        let site = tempfile::tempdir().unwrap();
+        let build_directory = site.path().join("build");
+        std::fs::create_dir_all(&build_directory).unwrap();
+        std::fs::write(build_directory.join("old.html"), "old").unwrap();
+
+        let staging = StagingDirectory::create(&build_directory);
+        std::fs::write(staging.path().join("new.html"), "new").unwrap();
+        staging.commit();
+
+        assert!(build_directory.join("new.html").exists());
+        assert!(!build_directory.join("old.html").exists());
+        assert_eq!(1, std::fs::read_dir(site.path()).unwrap().count());
+    }
+
+    #[test]
+    fn it_keeps_the_previous_build_when_not_committed() {
+        let site = tempfile::tempdir().unwrap();
+        let build_directory = site.path().join("build");
+        std::fs::create_dir_all(&build_directory).unwrap();
+        std::fs::write(build_directory.join("old.html"), "old").unwrap();
+
+        {
+            let staging = StagingDirectory::create(&build_directory);
+            std::fs::write(staging.path().join("new.html"), "new").unwrap();
+        }
+
+        assert!(build_directory.join("old.html").exists());
+        assert!(!build_directory.join("new.html").exists());
+        assert_eq!(1, std::fs::read_dir(site.path()).unwrap().count());
+    }
+
+    #[test]
+    fn it_carries_files_of_the_previous_build_over() {
+        let site = tempfile::tempdir().unwrap();
+        let build_directory = site.path().join("build");
+        std::fs::create_dir_all(build_directory.join("blog")).unwrap();
+        std::fs::write(build_directory.join("blog/post.html"), "post").unwrap();
+
+        let staging = StagingDirectory::create(&build_directory);
+        staging.carry_over(&build_directory.join("blog/post.html"), "blog/post.html");
+        staging.commit();
+
+        assert_eq!(
+            "post",
+            std::fs::read_to_string(build_directory.join("blog/post.html")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::theme::Theme;
+use crate::configuration::Config;
+use crate::utils;
+
+/// Directory, relative to a templates directory, containing the partials included with
+/// `{> name}`.
+pub const PARTIALS_DIRECTORY: &str = "partials";
+
+/// Directory, relative to a templates directory, copied as is into the build directory.
+pub const ASSETS_DIRECTORY: &str = "assets";
+
+const MAX_PARTIAL_DEPTH: usize = 16;
+
+/// Looks up templates, partials and assets in the project's templates directory first and in
+/// the theme's directory second, so any file of the project overrides the theme's copy.
+pub struct Templates {
+    /// Directories searched for templates, in order of precedence
+    directories: Vec<PathBuf>,
+}
+
+impl Templates {
+    pub fn new(config: &Config, theme: Option<&Theme>) -> Self {
+        let mut directories = vec![PathBuf::from(&config.templates_directory)];
+
+        if let Some(theme) = theme {
+            directories.push(theme.directory.clone());
+        }
+
+        Templates { directories }
+    }
+
+    /// Finds the file at `name`, relative to the templates directories.
+    pub fn find(&self, name: &Path) -> Option<PathBuf> {
+        self.directories
+            .iter()
+            .map(|directory| directory.join(name))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Loads the template at `name`, relative to the templates directories, with its partials
+    /// included.
+    pub fn load(&self, name: &Path) -> Option<String> {
+        let path = self.find(name)?;
+
+        log::debug!("Loading template {:?} from {:?}", name, path);
+
+        let template = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Could not read template {:?}: {}", path, e));
+
+        Some(self.include_partials(&template, 0))
+    }
+
+    /// Loads the template at `path`, falling back to the theme when `path` is inside the
+    /// project's templates directory but does not exist.
+    pub fn load_path(&self, path: &Path) -> Option<String> {
+        match path.strip_prefix(&self.directories[0]) {
+            Ok(name) => self.load(name),
+            Err(_) => {
+                let template = std::fs::read_to_string(path).ok()?;
+
+                Some(self.include_partials(&template, 0))
+            }
+        }
+    }
+
+    /// Name of the template at `path` relative to the project's templates directory, if it is
+    /// inside of it.
+    pub fn name_of(&self, path: &Path) -> Option<PathBuf> {
+        path.strip_prefix(&self.directories[0])
+            .ok()
+            .map(Path::to_path_buf)
+    }
+
+    /// Every file in the templates directories by path relative to its directory, with the
+    /// project's files taking precedence over the theme's.
+    pub fn files(&self) -> BTreeMap<PathBuf, PathBuf> {
+        let mut files = BTreeMap::new();
+
+        for directory in self.directories.iter().rev() {
+            for path in utils::list_files(directory) {
+                let name = path
+                    .strip_prefix(directory)
+                    .expect("Listed files are inside their directory")
+                    .to_path_buf();
+
+                files.insert(name, path);
+            }
+        }
+
+        files
+    }
+
+    fn include_partials(&self, template: &str, depth: usize) -> String {
+        lazy_static! {
+            static ref PARTIAL_REGEX: Regex = Regex::new(r"\{>\s*(?P<name>[^}\s]+)\s*\}").unwrap();
+        }
+
+        if depth > MAX_PARTIAL_DEPTH {
+            panic!("Partials are nested too deeply, do some of them include each other?");
+        }
+
+        PARTIAL_REGEX
+            .replace_all(template, |captures: &Captures| {
+                let mut name = Path::new(PARTIALS_DIRECTORY).join(&captures["name"]);
+                if name.extension().is_none() {
+                    name.set_extension("html");
+                }
+
+                let path = self
+                    .find(&name)
+                    .unwrap_or_else(|| panic!("Could not find partial {:?}", name));
+                let partial = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("Could not read partial {:?}: {}", path, e));
+
+                self.include_partials(&partial, depth + 1)
+            })
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod test_templates {
+    use super::Templates;
+    use std::path::{Path, PathBuf};
+
+    fn write(path: PathBuf, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn it_prefers_project_templates_over_theme_templates() {
+        let project = tempfile::tempdir().unwrap();
+        let theme = tempfile::tempdir().unwrap();
+        write(project.path().join("index.html"), "project {> header}");
+        write(theme.path().join("index.html"), "theme");
+        write(theme.path().join("blog.html"), "theme blog");
+        write(theme.path().join("partials/header.html"), "header");
+
+        let templates = Templates {
+            directories: vec![project.path().to_path_buf(), theme.path().to_path_buf()],
+        };
+
+        assert_eq!(
+            Some(String::from("project header")),
+            templates.load(Path::new("index.html"))
+        );
+        assert_eq!(
+            Some(String::from("theme blog")),
+            templates.load_path(&project.path().join("blog.html"))
+        );
+        assert_eq!(3, templates.files().len());
+    }
+}
@@ -0,0 +1,165 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use crate::configuration::Config;
+
+/// File at the root of a theme describing it.
+pub const THEME_METADATA_FILE: &str = "theme.toml";
+
+/// Features of just-html a theme can declare it relies on.
+pub const SUPPORTED_FEATURES: [&str; 13] = [
+    "content",
+    "listing",
+    "stylesheets",
+    "partials",
+    "assets",
+    "drafts",
+    "backlinks",
+    "search",
+    "pages",
+    "data",
+    "collections",
+    "feeds",
+    "series",
+];
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ThemeMetadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Dotted configuration keys that must be set for the theme to work, e.g. `content_dir`
+    pub required_config: Vec<String>,
+    /// Features of just-html the theme relies on, see `SUPPORTED_FEATURES`
+    pub features: Vec<String>,
+}
+
+/// A theme installed in `themes_directory`, providing templates, partials and assets that the
+/// project's own templates directory can override file by file.
+pub struct Theme {
+    pub name: String,
+    pub directory: PathBuf,
+    pub metadata: ThemeMetadata,
+}
+
+impl Theme {
+    /// Loads the theme selected in the configuration, if any.
+    pub fn load(config: &Config) -> Option<Self> {
+        let name = config.theme.as_ref()?;
+        let directory = PathBuf::from(&config.themes_directory).join(name);
+
+        if !directory.is_dir() {
+            panic!(
+                "Could not find theme {:?}, expected it in {:?}",
+                name, directory
+            );
+        }
+
+        let metadata_path = directory.join(THEME_METADATA_FILE);
+        let metadata = match metadata_path.exists() {
+            true => {
+                let contents =
+                    std::fs::read_to_string(&metadata_path).expect("Could not read theme metadata");
+
+                toml::from_str(&contents).unwrap_or_else(|e| {
+                    panic!("Failed to parse theme metadata {:?}: {}", metadata_path, e)
+                })
+            }
+            false => {
+                log::debug!("Theme {:?} has no {}", name, THEME_METADATA_FILE);
+                ThemeMetadata::default()
+            }
+        };
+
+        log::info!("Using theme {:?} from {:?}", name, directory);
+
+        Some(Theme {
+            name: String::from(name),
+            directory,
+            metadata,
+        })
+    }
+
+    /// Checks that the configuration sets every key the theme requires and that every feature
+    /// it relies on is supported.
+    pub fn validate(&self, config: &Config) {
+        let problems = self.problems(config);
+
+        if !problems.is_empty() {
+            panic!(
+                "Theme {:?} cannot be used with this site:\n  {}",
+                self.name,
+                problems.join("\n  ")
+            );
+        }
+    }
+
+    /// A required key left at its default counts as missing, as does a table none of whose keys
+    /// are set.
+    fn problems(&self, config: &Config) -> Vec<String> {
+        let missing_keys = self
+            .metadata
+            .required_config
+            .iter()
+            .filter(|key| {
+                let table_prefix = format!("{}.", key);
+                !config
+                    .set_keys
+                    .iter()
+                    .any(|set_key| set_key == *key || set_key.starts_with(&table_prefix))
+            })
+            .map(|key| format!("Missing required configuration key {:?}", key));
+
+        let unsupported_features = self
+            .metadata
+            .features
+            .iter()
+            .filter(|feature| !SUPPORTED_FEATURES.contains(&feature.as_str()))
+            .map(|feature| format!("Unsupported feature {:?}", feature));
+
+        missing_keys.chain(unsupported_features).collect()
+    }
+}
+
+#[cfg(test)]
+mod test_theme {
+    use super::{Theme, ThemeMetadata};
+    use crate::configuration::Config;
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    #[test]
+    fn it_reports_missing_configuration_keys_and_unsupported_features() {
+        let theme = Theme {
+            name: String::from("test"),
+            directory: PathBuf::from("themes/test"),
+            metadata: ThemeMetadata {
+                required_config: vec![
+                    String::from("build_config.content_listing_page"),
+                    String::from("site"),
+                    String::from("site.author"),
+                    String::from("theme_color"),
+                ],
+                features: vec![String::from("listing"), String::from("comments")],
+                ..ThemeMetadata::default()
+            },
+        };
+
+        let config = Config {
+            set_keys: BTreeSet::from([
+                String::from("build_config.content_listing_page"),
+                String::from("site.title"),
+            ]),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            vec![
+                String::from("Missing required configuration key \"site.author\""),
+                String::from("Missing required configuration key \"theme_color\""),
+                String::from("Unsupported feature \"comments\""),
+            ],
+            theme.problems(&config)
+        );
+    }
+}
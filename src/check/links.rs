@@ -0,0 +1,350 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::utils;
+
+/// The files of a built site, by path relative to the build directory and separated by `/`,
+/// with the contents of its HTML pages.
+#[derive(Default)]
+pub struct Site {
+    files: BTreeSet<String>,
+    pages: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BrokenLink {
+    /// Page containing the link
+    pub page: String,
+    pub line: usize,
+    pub href: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: broken link {:?}, {}",
+            self.page, self.line, self.href, self.reason
+        )
+    }
+}
+
+impl Site {
+    pub fn new() -> Self {
+        Site::default()
+    }
+
+    /// Loads every file of the build directory.
+    pub fn from_directory(build_directory: &Path) -> Self {
+        let mut site = Site::new();
+
+        for path in utils::list_files(build_directory) {
+            let name = utils::to_url_path(
+                path.strip_prefix(build_directory)
+                    .expect("Listed files are inside their directory"),
+            );
+
+            match is_html(&name) {
+                true => {
+                    let contents = std::fs::read_to_string(&path)
+                        .unwrap_or_else(|e| panic!("Could not read {:?}: {}", path, e));
+                    site.add_page(name, contents);
+                }
+                false => site.add_file(name),
+            }
+        }
+
+        site
+    }
+
+    pub fn add_file(&mut self, path: String) {
+        self.files.insert(path);
+    }
+
+    pub fn add_page(&mut self, path: String, contents: String) {
+        self.files.insert(path.clone());
+        self.pages.insert(path, contents);
+    }
+
+    /// Finds every `href` and `src` attribute of the site's pages that points inside the site at
+    /// a file, or an anchor, that does not exist. Escaped text, e.g. HTML shown in a code block,
+    /// is not a link. Links to other sites are not checked, except those
+    /// starting with `base_url`.
+    pub fn broken_links(&self, base_url: &str) -> Vec<BrokenLink> {
+        // The attributes of a tag are skipped whole, so text within quoted values is not a link
+        lazy_static! {
+            static ref LINK_REGEX: Regex = Regex::new(
+                r#"(?i)<[a-z][\w-]*(?:\s+[^\s"'<>/=]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'<>]+))?)*?\s+(?:href|src)\s*=\s*(?:"(?P<double>[^"]*)"|'(?P<single>[^']*)'|(?P<bare>[^\s"'>]+))"#
+            )
+            .unwrap();
+        }
+
+        let base_path = base_path(base_url);
+        let mut broken_links = Vec::new();
+
+        for (page, contents) in &self.pages {
+            for captures in LINK_REGEX.captures_iter(contents) {
+                let link = captures
+                    .name("double")
+                    .or_else(|| captures.name("single"))
+                    .or_else(|| captures.name("bare"))
+                    .expect("One of the link alternatives always matches");
+
+                if let Some(reason) = self.check_link(page, link.as_str(), base_url, &base_path) {
+                    broken_links.push(BrokenLink {
+                        page: page.clone(),
+                        line: line_number(contents, link.start()),
+                        href: String::from(link.as_str()),
+                        reason,
+                    });
+                }
+            }
+        }
+
+        broken_links
+    }
+
+    /// Returns why the link is broken, if it is.
+    fn check_link(
+        &self,
+        page: &str,
+        href: &str,
+        base_url: &str,
+        base_path: &str,
+    ) -> Option<String> {
+        let href = match href.strip_prefix(base_url) {
+            Some(path) if base_url.contains("://") => format!("/{}", path),
+            _ => String::from(href),
+        };
+
+        if href.is_empty() || utils::is_external_url(&href) {
+            return None;
+        }
+
+        let (path, fragment) = match href.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (href.as_str(), None),
+        };
+        let path = path.split('?').next().unwrap_or_default();
+
+        let target = match path {
+            "" => String::from(page),
+            _ => {
+                let resolved = match path.strip_prefix('/') {
+                    Some(root_relative) => {
+                        let root_relative = format!("/{}", root_relative);
+                        match root_relative.strip_prefix(base_path) {
+                            Some(inside_site) => String::from(inside_site),
+                            None => return Some(String::from("points outside the site")),
+                        }
+                    }
+                    None => {
+                        let directory = page.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+                        format!("{}/{}", directory, path)
+                    }
+                };
+
+                match self.resolve(&percent_decode(&resolved)) {
+                    Some(target) => target,
+                    None => return Some(String::from("file not found")),
+                }
+            }
+        };
+
+        match fragment {
+            Some(fragment) if !fragment.is_empty() => match self.pages.get(&target) {
+                Some(contents) if has_anchor(contents, fragment) => None,
+                Some(_) => Some(format!("anchor #{} not found in {}", fragment, target)),
+                None => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Finds the file a path points to, normalising `.` and `..` and serving directories from
+    /// their `index.html`.
+    fn resolve(&self, path: &str) -> Option<String> {
+        let mut segments: Vec<&str> = Vec::new();
+
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop()?;
+                }
+                segment => segments.push(segment),
+            }
+        }
+
+        let path = segments.join("/");
+        let index = match path.as_str() {
+            "" => String::from("index.html"),
+            _ => format!("{}/index.html", path),
+        };
+
+        [path, index]
+            .into_iter()
+            .find(|candidate| self.files.contains(candidate))
+    }
+}
+
+pub fn is_html(path: &str) -> bool {
+    path.ends_with(".html") || path.ends_with(".htm")
+}
+
+/// Path the site is served from, according to its base URL, e.g. `/docs/` for
+/// `https://example.com/docs`.
+fn base_path(base_url: &str) -> String {
+    let path = match base_url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|start| &rest[start..]).unwrap_or("/"),
+        None => base_url,
+    };
+
+    format!("/{}/", path.trim_matches('/')).replace("//", "/")
+}
+
+fn has_anchor(contents: &str, fragment: &str) -> bool {
+    lazy_static! {
+        static ref ANCHOR_REGEX: Regex = Regex::new(
+            r#"(?i)<[a-z][\w-]*(?:\s+[^\s"'<>/=]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'<>]+))?)*?\s+(?:id|name)\s*=\s*(?:"(?P<double>[^"]*)"|'(?P<single>[^']*)'|(?P<bare>[^\s"'>]+))"#
+        )
+        .unwrap();
+    }
+
+    let fragment = percent_decode(fragment);
+
+    ANCHOR_REGEX.captures_iter(contents).any(|captures| {
+        captures
+            .name("double")
+            .or_else(|| captures.name("single"))
+            .or_else(|| captures.name("bare"))
+            .map(|anchor| anchor.as_str() == fragment)
+            .unwrap_or(false)
+    })
+}
+
+fn line_number(contents: &str, offset: usize) -> usize {
+    contents[..offset].matches('\n').count() + 1
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = match bytes[index] {
+            b'%' if index + 2 < bytes.len() => std::str::from_utf8(&bytes[index + 1..index + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod test_links {
+    use super::{base_path, percent_decode, Site};
+
+    fn site() -> Site {
+        let mut site = Site::new();
+        site.add_file(String::from("style.css"));
+        site.add_page(
+            String::from("index.html"),
+            String::from(
+                r##"<link rel="stylesheet" href="/style.css" />
+<a href=blog/post.html>Post</a>
+<a href="https://example.com/missing.html">External</a>
+<a href="/blog/">Blog</a>
+<a href="blog/missing.html">Missing</a>"##,
+            ),
+        );
+        site.add_page(
+            String::from("blog/index.html"),
+            String::from(r##"<a href="../index.html">Home</a>"##),
+        );
+        site.add_page(
+            String::from("blog/post.html"),
+            String::from(
+                r##"<h2 id="intro">Intro</h2>
+<a href="#intro">Intro</a>
+<a href='#outro'>Outro</a>
+<img src="../images/cat.png" />
+<a href="https://blog.example.com/blog/post.html#intro">Self</a>"##,
+            ),
+        );
+        site
+    }
+
+    #[test]
+    fn it_reports_missing_files_and_anchors_with_their_line() {
+        let broken_links = site().broken_links("https://blog.example.com/");
+
+        let reported: Vec<(&str, usize, &str)> = broken_links
+            .iter()
+            .map(|link| (link.page.as_str(), link.line, link.href.as_str()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("blog/post.html", 3, "#outro"),
+                ("blog/post.html", 4, "../images/cat.png"),
+                ("index.html", 5, "blog/missing.html"),
+            ],
+            reported
+        );
+    }
+
+    #[test]
+    fn it_ignores_escaped_html() {
+        let mut site = Site::new();
+        site.add_page(
+            String::from("blog/post.html"),
+            String::from(
+                r##"<meta name="description" content="Write &lt;a href=&quot;missing.html&quot;&gt;" />
+<pre><code>&lt;a href="missing.html"&gt;Missing&lt;/a&gt; &lt;img src=cat.png&gt;</code></pre>"##,
+            ),
+        );
+
+        assert!(site.broken_links("/").is_empty());
+    }
+
+    #[test]
+    fn it_only_checks_root_relative_links_under_the_base_path() {
+        let broken_links = site().broken_links("https://example.com/docs/");
+
+        assert!(broken_links
+            .iter()
+            .any(|link| link.href == "/style.css" && link.reason == "points outside the site"));
+    }
+
+    #[test]
+    fn it_extracts_the_base_path_of_the_base_url() {
+        assert_eq!("/", base_path("/"));
+        assert_eq!("/", base_path("https://example.com"));
+        assert_eq!("/docs/", base_path("https://example.com/docs"));
+        assert_eq!("/docs/", base_path("/docs/"));
+    }
+
+    #[test]
+    fn it_decodes_percent_encoded_characters() {
+        assert_eq!("my post.html", percent_decode("my%20post.html"));
+        assert_eq!("100%", percent_decode("100%"));
+    }
+}
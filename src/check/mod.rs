@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use crate::build::lint;
+use crate::configuration::Config;
+use clap::{Parser, Subcommand};
+
+pub mod links;
+
+#[derive(Parser)]
+pub struct CheckCommand {
+    #[clap(short, long, global = true)]
+    debug: bool,
+
+    #[clap(subcommand)]
+    command: CheckCommands,
+}
+
+#[derive(Subcommand)]
+enum CheckCommands {
+    /// Check the links between the pages of the build directory
+    Links,
+    /// List the variables used by every template and check that each one is defined
+    Templates,
+}
+
+pub fn command(command: &CheckCommand, config: &Config) {
+    match command.command {
+        CheckCommands::Links => check_links(config),
+        CheckCommands::Templates => check_templates(config),
+    }
+}
+
+fn check_links(config: &Config) {
+    let build_directory = Path::new(&config.build_config.build_directory);
+
+    if !build_directory.exists() {
+        log::error!(
+            "Build directory {:?} not found, build the site first",
+            build_directory
+        );
+        std::process::exit(1);
+    }
+
+    let broken_links =
+        links::Site::from_directory(build_directory).broken_links(&config.site.base_url);
+
+    for broken_link in &broken_links {
+        println!("{}", broken_link);
+    }
+
+    if !broken_links.is_empty() {
+        log::error!("Found {} broken link(s)", broken_links.len());
+        std::process::exit(1);
+    }
+
+    log::info!("No broken links found in {:?}", build_directory);
+}
+
+fn check_templates(config: &Config) {
+    let template_lint = lint::lint_templates(config);
+
+    for (template, variables) in &template_lint.usages {
+        let variables: Vec<&str> = variables.iter().map(String::as_str).collect();
+        println!("{}: {}", template, variables.join(", "));
+    }
+
+    for problem in &template_lint.problems {
+        println!("{}", problem);
+    }
+
+    if !template_lint.problems.is_empty() {
+        log::error!("Found {} template problem(s)", template_lint.problems.len());
+        std::process::exit(1);
+    }
+
+    log::info!("Every template variable is defined");
+}
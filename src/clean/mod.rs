@@ -0,0 +1,40 @@
+use std::path::Path;
+
+use crate::build::manifest;
+use crate::configuration::Config;
+use clap::Parser;
+
+#[derive(Parser)]
+pub struct CleanCommand {
+    #[clap(short, long, global = true)]
+    debug: bool,
+
+    /// Remove the whole build directory, including files that were not built by just-html
+    #[clap(long)]
+    all: bool,
+}
+
+pub fn command(command: &CleanCommand, config: &Config) {
+    let build_directory = Path::new(&config.build_config.build_directory);
+
+    if !build_directory.exists() {
+        log::info!("Nothing to clean in {:?}", build_directory);
+        return;
+    }
+
+    if command.all {
+        log::info!("Removing build directory {:?}", build_directory);
+        std::fs::remove_dir_all(build_directory).expect("Failed to remove build directory");
+        return;
+    }
+
+    log::info!("Removing built files from {:?}", build_directory);
+
+    let built_files = manifest::Manifest::load(build_directory);
+    manifest::remove_files(build_directory, built_files.files.iter());
+
+    let manifest_path = build_directory.join(manifest::MANIFEST_FILE);
+    if manifest_path.exists() {
+        std::fs::remove_file(manifest_path).expect("Failed to remove build manifest");
+    }
+}
@@ -14,8 +14,12 @@ pub struct ConfigCommand {
     #[clap(subcommand)]
     command: Option<ConfigCommands>,
 
-    #[clap(short = 'f', long = "file", global = true)]
+    #[clap(short = 'f', long = "file", global = true, value_parser = crate::parse_configuration_file_path)]
     configuration_file_path: Option<String>,
+
+    /// Configuration profile to apply, e.g. `production` for `[profile.production]`
+    #[clap(long, global = true)]
+    profile: Option<String>,
 }
 
 impl super::ConfigurationFilePath for ConfigCommand {
@@ -28,6 +32,14 @@ impl super::ConfigurationFilePath for ConfigCommand {
 pub enum ConfigCommands {
     /// Validate the configuration
     Validate,
+    /// Print the configuration
+    Show {
+        /// Apply the profile and environment variables, showing where every value comes from
+        #[clap(long)]
+        resolved: bool,
+    },
+    /// Print the JSON Schema of the configuration file, for editor autocompletion
+    Schema,
 }
 
 pub fn command(config_command: &ConfigCommand) {
@@ -35,7 +47,17 @@ pub fn command(config_command: &ConfigCommand) {
 
     match config_command.command.as_ref() {
         Some(ConfigCommands::Validate) => {
-            validate(config_command.path());
+            validate(config_command.path(), config_command.profile.as_deref());
+        }
+        Some(ConfigCommands::Show { resolved }) => {
+            show(
+                config_command.path(),
+                config_command.profile.as_deref(),
+                *resolved,
+            );
+        }
+        Some(ConfigCommands::Schema) => {
+            schema();
         }
         None => {
             log::info!("Unknown command")
@@ -43,9 +65,37 @@ pub fn command(config_command: &ConfigCommand) {
     }
 }
 
-fn validate(path: PathBuf) {
+fn validate(path: PathBuf, profile: Option<&str>) {
     log::info!("Validating config");
-    let conf = configuration::Config::load(path);
+    let conf = configuration::Config::load(path, profile);
 
     log::info!("{:?}", conf);
 }
+
+fn show(path: PathBuf, profile: Option<&str>, resolved: bool) {
+    if !resolved {
+        let contents = std::fs::read_to_string(&path).expect("Could not load configuration file");
+        let conf = configuration::Config::parse(&contents).expect("Failed to parse configuration");
+
+        print!(
+            "{}",
+            toml::to_string(&conf).expect("Failed to serialize configuration")
+        );
+        return;
+    }
+
+    let resolved_config = configuration::Config::resolve(path, profile);
+
+    for (key, value, source) in resolved_config.entries() {
+        println!("{} = {} # {}", key, value, source);
+    }
+}
+
+fn schema() {
+    let schema = configuration::Config::schema();
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&schema).expect("Failed to serialize configuration schema")
+    );
+}
@@ -0,0 +1,944 @@
+use schemars::gen::SchemaSettings;
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+
+pub mod sources;
+
+pub use sources::ResolvedConfig;
+
+/// Configuration of a just-html site, usually stored in `config.toml`.
+///
+/// Every key has a default, so an empty configuration file is a valid one.
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(default)]
+pub struct Config {
+    /// Directory containing index.html, the listing page template and stylesheets
+    pub templates_directory: String,
+    /// Template used to render every content file, must contain `{content}`
+    pub content_template: String,
+    /// Directory containing the Markdown content files
+    pub content_dir: String,
+    /// Directory containing standalone Markdown pages, e.g. `about.md`, built at the root of
+    /// the site with the `page.html` template, or the content template if there is none
+    pub pages_directory: String,
+    /// Directory containing JSON, TOML, YAML and CSV data files, available to templates as
+    /// `{data.<file name>}`
+    pub data_directory: String,
+    /// Theme providing the templates, partials and assets missing from `templates_directory`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    /// Directory containing the installed themes, one directory per theme
+    pub themes_directory: String,
+    /// Directory containing the archetypes used by the `new` command, e.g. `post.md`
+    pub archetypes_directory: String,
+    pub site: SiteConfig,
+    pub build_config: BuildConfig,
+    pub development_config: DevelopmentConfig,
+    pub search: SearchConfig,
+    /// Sections of the site, each built from its own content directory, e.g. a blog and
+    /// projects. When there is none, `content_dir`, `content_template`,
+    /// `build_config.content_directory` and `build_config.content_listing_page` describe the
+    /// only collection
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub collections: Vec<CollectionConfig>,
+    /// Default values of template variables missing from a page's frontmatter, e.g.
+    /// `author = "Anonymous"`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub defaults: BTreeMap<String, String>,
+    /// Arbitrary values available to templates as `{params.<key>}`, e.g. `{params.twitter}`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, serde_json::Value>,
+    /// Dotted keys set by the configuration file, a profile or an environment variable, rather
+    /// than left at their default
+    #[serde(skip)]
+    pub set_keys: BTreeSet<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            templates_directory: String::from("./templates"),
+            content_template: String::from("./templates/content.html"),
+            content_dir: String::from("./content"),
+            pages_directory: String::from("./pages"),
+            data_directory: String::from("./data"),
+            theme: None,
+            themes_directory: String::from("./themes"),
+            archetypes_directory: String::from("./archetypes"),
+            site: SiteConfig::default(),
+            build_config: BuildConfig::default(),
+            development_config: DevelopmentConfig::default(),
+            search: SearchConfig::default(),
+            collections: Vec::new(),
+            defaults: BTreeMap::new(),
+            params: BTreeMap::new(),
+            set_keys: BTreeSet::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(default)]
+pub struct SiteConfig {
+    /// Title of the website
+    pub title: String,
+    /// URL the website is served from, e.g. `https://example.com/`
+    pub base_url: String,
+    /// Default author of new content files
+    pub author: String,
+    /// Any other key, e.g. `description`, available to templates as `{site.<key>}`
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        SiteConfig {
+            title: String::from("My just-html site"),
+            base_url: String::from("/"),
+            author: String::new(),
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(default)]
+pub struct BuildConfig {
+    /// Directory the website is built into
+    pub build_directory: String,
+    /// Directory the content pages are built into
+    pub content_directory: String,
+    /// Name of the listing page, without the `.html` extension
+    pub content_listing_page: String,
+    /// Build content files marked with `draft: true` in their frontmatter
+    pub drafts: bool,
+    /// Number of content pages listed by the `{recent_posts}` template variable
+    pub recent_posts: usize,
+    /// Maximum number of words of the summaries taken from the first paragraph of a page
+    pub summary_words: usize,
+    /// Reading speed used to estimate the `{reading_time}` of content pages, in minutes
+    pub words_per_minute: usize,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        BuildConfig {
+            build_directory: String::from("./build"),
+            content_directory: String::from("./build/blog"),
+            content_listing_page: String::from("blog"),
+            drafts: false,
+            recent_posts: 5,
+            summary_words: 50,
+            words_per_minute: 200,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(default)]
+pub struct DevelopmentConfig {
+    /// Port used by the `serve` command
+    pub port: u16,
+}
+
+impl Default for DevelopmentConfig {
+    fn default() -> Self {
+        DevelopmentConfig { port: 9999 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// Build a search index of the content pages for client-side search, off by default
+    pub enabled: bool,
+    /// Path of the search index, relative to the build directory
+    pub index_file: String,
+    /// Parts of the content pages to index
+    pub fields: Vec<SearchField>,
+    /// Words left out of the indexed text
+    pub stop_words: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    /// The `title` of the frontmatter
+    Title,
+    /// The comma separated `tags` of the frontmatter
+    Tags,
+    Headings,
+    /// The plain text of the page, without its code blocks
+    Body,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            enabled: false,
+            index_file: String::from("search-index.json"),
+            fields: vec![
+                SearchField::Title,
+                SearchField::Tags,
+                SearchField::Headings,
+                SearchField::Body,
+            ],
+            stop_words: [
+                "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into",
+                "is", "it", "no", "not", "of", "on", "or", "such", "that", "the", "their", "then",
+                "there", "these", "they", "this", "to", "was", "will", "with",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(default)]
+pub struct CollectionConfig {
+    /// Name of the collection, e.g. `projects`
+    pub name: String,
+    /// Directory containing the Markdown files of the collection
+    pub content_dir: String,
+    /// Template used to render every file of the collection, must contain `{content}`
+    pub content_template: String,
+    /// Directory the pages of the collection are built into, relative to the build directory
+    pub output_directory: String,
+    /// Name of the listing page template, without the `.html` extension
+    pub listing_page: String,
+    /// Order of the pages in the listing page and the feed
+    pub sort: SortOrder,
+    /// Path of the Atom feed of the collection, relative to the build directory, e.g.
+    /// `projects.xml`. No feed is built when it is not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed: Option<String>,
+}
+
+impl Default for CollectionConfig {
+    fn default() -> Self {
+        CollectionConfig {
+            name: String::from("blog"),
+            content_dir: String::from("./content"),
+            content_template: String::from("./templates/content.html"),
+            output_directory: String::from("blog"),
+            listing_page: String::from("blog"),
+            sort: SortOrder::default(),
+            feed: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortOrder {
+    /// By file name
+    #[default]
+    FileName,
+    /// By the `date` of the frontmatter, oldest first
+    Date,
+    /// By the `date` of the frontmatter, newest first
+    DateDescending,
+    /// By the `title` of the frontmatter
+    Title,
+}
+
+impl Config {
+    pub fn load(configuration_file_path: PathBuf, profile: Option<&str>) -> Self {
+        Config::resolve(configuration_file_path, profile).config
+    }
+
+    /// Loads the configuration file and applies the selected profile and any `JHT_*`
+    /// environment variable on top of it, keeping track of where every value came from.
+    ///
+    /// When no profile is given, the one named by `JHT_PROFILE` is used, if set.
+    pub fn resolve(configuration_file_path: PathBuf, profile: Option<&str>) -> ResolvedConfig {
+        let config_location = ConfigLocation::new(configuration_file_path);
+
+        log::debug!("Loading config from: {:?}", config_location);
+
+        match config_location.config_directory.exists() {
+            true => {}
+            false => {
+                panic!("Could not find config directory, please run just-html init to initialise the configuration")
+            }
+        }
+
+        match config_location.config_file_path.exists() {
+            true => {}
+            false => {
+                panic!("Could not find config file, please run just-html init to initialise the configuration")
+            }
+        }
+
+        let contents = std::fs::read_to_string(&config_location.config_file_path)
+            .expect("Could not load configuration file");
+
+        let profile = profile
+            .map(String::from)
+            .or_else(|| std::env::var(sources::PROFILE_ENVIRONMENT_VARIABLE).ok());
+
+        let mut resolved = ResolvedConfig::new(
+            &contents,
+            &config_location.config_file_path,
+            profile.as_deref(),
+            std::env::vars(),
+        )
+        .unwrap_or_else(|e| {
+            panic!(
+                "Failed to parse configuration in {:?}: {}",
+                config_location.config_file_path, e
+            )
+        });
+
+        resolved
+            .config
+            .resolve_paths(&config_location.config_directory);
+
+        log::debug!("Loaded config: {:?}", resolved.config);
+
+        resolved
+    }
+
+    /// Exits when `init` would refuse to touch an existing configuration in `mode`, so it can be
+    /// checked before asking for the values of a new configuration.
+    pub fn check_init(configuration_file_path: &Path, mode: InitMode) {
+        if mode == InitMode::Create && configuration_file_path.exists() {
+            log::error!(
+                "Found an existing configuration in {:?}, run init with --force to overwrite it or with --merge to only add missing keys",
+                configuration_file_path
+            );
+            std::process::exit(1);
+        }
+    }
+
+    /// Writes `config` to a new configuration file, or combines it with the existing one
+    /// according to `mode`.
+    pub fn init(configuration_file_path: PathBuf, mode: InitMode, config: Config) -> Self {
+        Config::check_init(&configuration_file_path, mode);
+        let config_location = ConfigLocation::new(configuration_file_path);
+
+        match config_location.config_directory.exists() {
+            true => {}
+            false => {
+                std::fs::create_dir_all(&config_location.config_directory)
+                    .expect("Failed to create config directory");
+            }
+        }
+
+        let contents = match (config_location.config_file_path.exists(), mode) {
+            (false, _) | (true, InitMode::Create) => {
+                toml::to_string(&config).expect("Failed to serialize default config")
+            }
+            (true, InitMode::Force) => {
+                backup_config_file(&config_location.config_file_path);
+                toml::to_string(&config).expect("Failed to serialize default config")
+            }
+            (true, InitMode::Merge) => {
+                backup_config_file(&config_location.config_file_path);
+                merge_config_file(&config_location.config_file_path, config)
+            }
+        };
+
+        log::debug!("Writing config: {:?}", contents);
+
+        std::fs::write(&config_location.config_file_path, &contents)
+            .expect("Failed to write default config to file.");
+
+        let mut config = Config::parse(&contents).unwrap_or_else(|e| {
+            panic!(
+                "Failed to parse configuration in {:?}: {}",
+                config_location.config_file_path, e
+            )
+        });
+        config.resolve_paths(&config_location.config_directory);
+
+        config
+    }
+
+    /// Makes every relative path in the configuration relative to the directory containing the
+    /// configuration file instead of the current directory.
+    pub fn resolve_paths(&mut self, config_directory: &Path) {
+        for path in [
+            &mut self.templates_directory,
+            &mut self.content_template,
+            &mut self.content_dir,
+            &mut self.pages_directory,
+            &mut self.data_directory,
+            &mut self.themes_directory,
+            &mut self.archetypes_directory,
+            &mut self.build_config.build_directory,
+            &mut self.build_config.content_directory,
+        ] {
+            *path = resolve_path(config_directory, path);
+        }
+
+        for collection in &mut self.collections {
+            for path in [
+                &mut collection.content_dir,
+                &mut collection.content_template,
+            ] {
+                *path = resolve_path(config_directory, path);
+            }
+        }
+    }
+
+    /// The collections to build, or the one described by the top-level content keys when
+    /// `collections` is empty. A `build_config.content_directory` outside of the build directory
+    /// is replaced with the directory of the same name inside it, with a warning.
+    pub fn collections(&self) -> Vec<CollectionConfig> {
+        if !self.collections.is_empty() {
+            return self.collections.clone();
+        }
+
+        let build_directory = Path::new(&self.build_config.build_directory);
+        let content_directory = Path::new(&self.build_config.content_directory);
+        let output_directory = match content_directory.strip_prefix(build_directory) {
+            Ok(output_directory) => output_directory,
+            Err(_) => {
+                let output_directory = Path::new(content_directory.file_name().unwrap_or_default());
+                log::warn!(
+                    "The content directory {:?} is outside the build directory {:?}, building the content pages into {:?} instead",
+                    content_directory,
+                    build_directory,
+                    build_directory.join(output_directory)
+                );
+                output_directory
+            }
+        };
+
+        vec![CollectionConfig {
+            name: self.build_config.content_listing_page.clone(),
+            content_dir: self.content_dir.clone(),
+            content_template: self.content_template.clone(),
+            output_directory: output_directory.to_string_lossy().to_string(),
+            listing_page: self.build_config.content_listing_page.clone(),
+            sort: SortOrder::default(),
+            feed: None,
+        }]
+    }
+
+    /// Parses the contents of a configuration file, warning about any key that is not part of
+    /// the configuration schema. Profiles and environment variables are not applied.
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        let resolved = ResolvedConfig::new(
+            contents,
+            std::path::Path::new("config.toml"),
+            None,
+            std::iter::empty(),
+        )?;
+
+        Ok(resolved.config)
+    }
+
+    /// JSON Schema describing the configuration file, used for editor autocompletion.
+    pub fn schema() -> RootSchema {
+        schemars::schema_for!(Config)
+    }
+}
+
+/// What `init` does when a configuration file already exists.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InitMode {
+    /// Refuse to touch the existing configuration
+    Create,
+    /// Back up the existing configuration and replace it
+    Force,
+    /// Back up the existing configuration and add the keys it is missing
+    Merge,
+}
+
+/// Copies the configuration file next to itself, as `config.toml.bak` or the first
+/// `config.toml.bak.<n>` that does not exist yet.
+fn backup_config_file(config_file_path: &Path) -> PathBuf {
+    let backup_path = (0..)
+        .map(|attempt| {
+            let mut file_name = config_file_path
+                .file_name()
+                .expect("Configuration file path has no file name")
+                .to_os_string();
+            file_name.push(".bak");
+            if attempt > 0 {
+                file_name.push(format!(".{}", attempt));
+            }
+            config_file_path.with_file_name(file_name)
+        })
+        .find(|candidate| !candidate.exists())
+        .expect("Ran out of backup file names");
+
+    std::fs::copy(config_file_path, &backup_path).expect("Failed to back up configuration file");
+
+    log::info!(
+        "Backed up {:?} to {:?}",
+        config_file_path.file_name().unwrap_or_default(),
+        backup_path
+    );
+
+    backup_path
+}
+
+/// Adds the keys of `defaults` missing from the configuration file, returning its new contents.
+/// Everything else in the file is kept as it is, including profiles and unknown keys.
+fn merge_config_file(config_file_path: &Path, defaults: Config) -> String {
+    let contents =
+        std::fs::read_to_string(config_file_path).expect("Could not load configuration file");
+    let mut existing: toml::Value = toml::from_str(&contents).unwrap_or_else(|e| {
+        panic!(
+            "Failed to parse configuration in {:?}: {}",
+            config_file_path, e
+        )
+    });
+    let defaults = toml::Value::try_from(defaults).expect("Failed to serialize default config");
+
+    for key in add_missing_keys(&mut existing, defaults, "") {
+        log::info!("Adding missing configuration key {:?}", key);
+    }
+
+    toml::to_string(&existing).expect("Failed to serialize merged config")
+}
+
+/// Adds every key of `defaults` that is missing from `existing`, keeping existing values.
+/// Returns the dotted paths of the added keys.
+fn add_missing_keys(existing: &mut toml::Value, defaults: toml::Value, path: &str) -> Vec<String> {
+    let (existing_table, defaults_table) = match (existing.as_table_mut(), defaults) {
+        (Some(existing_table), toml::Value::Table(defaults_table)) => {
+            (existing_table, defaults_table)
+        }
+        _ => return Vec::new(),
+    };
+
+    let mut added_keys = Vec::new();
+
+    for (key, default_value) in defaults_table {
+        let key_path = match path {
+            "" => key.clone(),
+            _ => format!("{}.{}", path, key),
+        };
+
+        match existing_table.get_mut(&key) {
+            Some(existing_value) => {
+                added_keys.extend(add_missing_keys(existing_value, default_value, &key_path))
+            }
+            None => {
+                existing_table.insert(key, default_value);
+                added_keys.push(key_path);
+            }
+        }
+    }
+
+    added_keys
+}
+
+/// Names of the configuration files looked for when no file is given with `-f`, in order of
+/// preference.
+pub const CONFIGURATION_FILE_NAMES: [&str; 2] = ["config.toml", "jht.toml"];
+
+/// Finds the configuration file of the project containing `start_directory`, looking in the
+/// directory itself and then in each of its parents.
+pub fn discover(start_directory: &Path) -> Option<PathBuf> {
+    start_directory.ancestors().find_map(|directory| {
+        CONFIGURATION_FILE_NAMES
+            .iter()
+            .map(|name| directory.join(name))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// Joins a relative `path` onto `base`, leaving absolute paths untouched.
+fn resolve_path(base: &Path, path: &str) -> String {
+    let path = Path::new(path);
+
+    if path.is_absolute() {
+        return path.to_string_lossy().to_string();
+    }
+
+    let mut resolved = base.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if resolved.file_name().is_some() => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+
+    resolved.to_string_lossy().to_string()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownKey {
+    /// Dotted path of the unknown key, e.g. `development_config.prot`
+    pub path: String,
+    /// Closest known key at the same level, if any is similar enough
+    pub suggestion: Option<String>,
+}
+
+impl std::fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown configuration key \"{}\"", self.path)?;
+
+        match &self.suggestion {
+            Some(suggestion) => write!(f, ", did you mean \"{}\"?", suggestion),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Lists every key of a parsed configuration file that does not appear in the configuration
+/// schema.
+pub fn find_unknown_keys(raw: &toml::Value) -> Vec<UnknownKey> {
+    let mut unknown_keys = Vec::new();
+    collect_unknown_keys(raw, &inline_schema(), "", &mut unknown_keys);
+
+    unknown_keys
+}
+
+/// The configuration schema with every definition inlined, so it can be walked key by key.
+fn inline_schema() -> serde_json::Value {
+    let schema = SchemaSettings::draft07()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator()
+        .into_root_schema_for::<Config>();
+
+    serde_json::to_value(schema).expect("Failed to serialize configuration schema")
+}
+
+/// Finds the schema of the value at the given dotted path, if the path is part of the schema.
+fn schema_at<'a>(schema: &'a serde_json::Value, path: &[&str]) -> Option<&'a serde_json::Value> {
+    match path.split_first() {
+        None => Some(schema),
+        Some((key, rest)) => {
+            let property = find_properties(schema)
+                .and_then(|properties| properties.get(*key))
+                .or_else(|| {
+                    // `true` accepts any value, e.g. for the arbitrary keys of `params`
+                    schema.get("additionalProperties").filter(|additional| {
+                        additional.is_object() || **additional == serde_json::Value::Bool(true)
+                    })
+                })?;
+
+            schema_at(property, rest)
+        }
+    }
+}
+
+fn collect_unknown_keys(
+    value: &toml::Value,
+    schema: &serde_json::Value,
+    path: &str,
+    unknown_keys: &mut Vec<UnknownKey>,
+) {
+    match value {
+        toml::Value::Table(table) => {
+            let properties = find_properties(schema);
+            let additional_properties = schema.get("additionalProperties").filter(|additional| {
+                additional.is_object() || **additional == serde_json::Value::Bool(true)
+            });
+
+            for (key, value) in table {
+                let key_path = match path {
+                    "" => key.clone(),
+                    _ => format!("{}.{}", path, key),
+                };
+
+                match (properties.and_then(|p| p.get(key)), additional_properties) {
+                    (Some(property_schema), _) => {
+                        collect_unknown_keys(value, property_schema, &key_path, unknown_keys)
+                    }
+                    (None, Some(additional_schema)) => {
+                        collect_unknown_keys(value, additional_schema, &key_path, unknown_keys)
+                    }
+                    (None, None) => {
+                        if let Some(properties) = properties {
+                            unknown_keys.push(UnknownKey {
+                                path: key_path,
+                                suggestion: suggest_key(key, properties.keys()),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        toml::Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    let item_path = format!("{}[{}]", path, index);
+                    collect_unknown_keys(item, item_schema, &item_path, unknown_keys);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Finds the properties of an object schema, looking through `anyOf` and friends so that
+/// optional tables are checked as well.
+fn find_properties(
+    schema: &serde_json::Value,
+) -> Option<&serde_json::Map<String, serde_json::Value>> {
+    if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+        return Some(properties);
+    }
+
+    ["anyOf", "oneOf", "allOf"]
+        .iter()
+        .filter_map(|combinator| schema.get(combinator).and_then(|s| s.as_array()))
+        .flatten()
+        .find_map(find_properties)
+}
+
+fn suggest_key<'a>(key: &str, candidates: impl Iterator<Item = &'a String>) -> Option<String> {
+    candidates
+        .map(|candidate| (strsim::jaro_winkler(key, candidate), candidate))
+        .filter(|(similarity, _)| *similarity > 0.8)
+        .max_by(|a, b| a.0.partial_cmp(&b.0).expect("Similarity is never NaN"))
+        .map(|(_, candidate)| candidate.clone())
+}
+
+#[derive(Debug)]
+struct ConfigLocation {
+    /// Full path of the configuration directory
+    config_directory: PathBuf,
+    /// Full path of the configuration file
+    config_file_path: PathBuf,
+}
+
+impl ConfigLocation {
+    pub fn new(input_config_path: PathBuf) -> Self {
+        log::debug!("Initializing ConfigLocation for {:?}", input_config_path);
+
+        let mut config_dir = input_config_path.clone();
+        config_dir.pop();
+
+        if config_dir.as_os_str().is_empty() {
+            config_dir.push(".");
+        }
+
+        ConfigLocation {
+            config_directory: config_dir,
+            config_file_path: input_config_path,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_configuration {
+    use super::{
+        add_missing_keys, discover, find_unknown_keys, resolve_path, Config, InitMode, SortOrder,
+        UnknownKey,
+    };
+    use std::path::Path;
+
+    #[test]
+    fn it_loads_an_empty_configuration_with_defaults() {
+        let config = Config::parse("").expect("Empty configuration should be valid");
+
+        assert_eq!("./build", config.build_config.build_directory);
+        assert_eq!(9999, config.development_config.port);
+    }
+
+    #[test]
+    fn it_fills_missing_keys_of_a_partial_table_with_defaults() {
+        let input = r#"
+[build_config]
+build_directory = "./public"
+"#;
+
+        let config = Config::parse(input).expect("Partial configuration should be valid");
+
+        assert_eq!("./public", config.build_config.build_directory);
+        assert_eq!("blog", config.build_config.content_listing_page);
+    }
+
+    #[test]
+    fn it_suggests_the_closest_key_for_unknown_keys() {
+        let input = r#"
+templates_directory = "./templates"
+content_dri = "./content"
+
+[development_config]
+prot = 8080
+"#;
+
+        let raw: toml::Value = toml::from_str(input).unwrap();
+
+        let unknown_keys = find_unknown_keys(&raw);
+
+        assert_eq!(
+            vec![
+                UnknownKey {
+                    path: String::from("content_dri"),
+                    suggestion: Some(String::from("content_dir")),
+                },
+                UnknownKey {
+                    path: String::from("development_config.prot"),
+                    suggestion: Some(String::from("port")),
+                },
+            ],
+            unknown_keys
+        );
+    }
+
+    #[test]
+    fn it_does_not_suggest_unrelated_keys() {
+        let raw: toml::Value = toml::from_str("banana = 1").unwrap();
+
+        let unknown_keys = find_unknown_keys(&raw);
+
+        assert_eq!(1, unknown_keys.len());
+        assert_eq!(None, unknown_keys[0].suggestion);
+    }
+
+    #[test]
+    fn it_accepts_arbitrary_site_and_params_keys() {
+        let input = r#"
+[site]
+title = "Notes"
+description = "What I learned"
+
+[params.social]
+twitter = "@notes"
+"#;
+
+        let raw: toml::Value = toml::from_str(input).unwrap();
+        assert!(find_unknown_keys(&raw).is_empty());
+
+        let config = Config::parse(input).unwrap();
+        assert_eq!("What I learned", config.site.extra["description"]);
+        assert_eq!("@notes", config.params["social"]["twitter"]);
+    }
+
+    #[test]
+    fn it_builds_the_top_level_content_as_the_only_collection_by_default() {
+        let collections = Config::default().collections();
+
+        assert_eq!(1, collections.len());
+        assert_eq!("blog", collections[0].output_directory);
+        assert_eq!("./content", collections[0].content_dir);
+
+        let input = r#"
+[[collections]]
+name = "projects"
+content_dir = "./projects"
+sort = "date-descending"
+feed = "projects.xml"
+"#;
+
+        let raw: toml::Value = toml::from_str(input).unwrap();
+        assert!(find_unknown_keys(&raw).is_empty());
+
+        let collections = Config::parse(input).unwrap().collections();
+        assert_eq!(1, collections.len());
+        assert_eq!("./projects", collections[0].content_dir);
+        assert_eq!(SortOrder::DateDescending, collections[0].sort);
+        assert_eq!(Some(String::from("projects.xml")), collections[0].feed);
+    }
+
+    #[test]
+    fn it_builds_a_content_directory_outside_the_build_directory_inside_it() {
+        let mut config = Config::default();
+        config.build_config.content_directory = String::from("./public/posts");
+
+        assert_eq!("posts", config.collections()[0].output_directory);
+    }
+
+    #[test]
+    fn it_resolves_relative_paths_against_the_configuration_directory() {
+        let base = Path::new("/home/site");
+
+        assert_eq!("/home/site/templates", resolve_path(base, "./templates"));
+        assert_eq!("/home/site/build/blog", resolve_path(base, "build/blog"));
+        assert_eq!("/home/shared", resolve_path(base, "../shared"));
+        assert_eq!("/var/www", resolve_path(base, "/var/www"));
+    }
+
+    #[test]
+    fn it_discovers_the_configuration_file_in_a_parent_directory() {
+        let project = tempfile::tempdir().unwrap();
+        let nested = project.path().join("content").join("posts");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(project.path().join("jht.toml"), "").unwrap();
+
+        assert_eq!(Some(project.path().join("jht.toml")), discover(&nested));
+    }
+
+    #[test]
+    fn it_prefers_config_toml_over_jht_toml() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join("jht.toml"), "").unwrap();
+        std::fs::write(project.path().join("config.toml"), "").unwrap();
+
+        assert_eq!(
+            Some(project.path().join("config.toml")),
+            discover(project.path())
+        );
+    }
+
+    #[test]
+    fn it_only_adds_missing_keys_when_merging_with_the_defaults() {
+        let mut existing: toml::Value = toml::from_str(
+            r#"
+content_dir = "./posts"
+
+[build_config]
+build_directory = "./public"
+"#,
+        )
+        .unwrap();
+        let defaults = toml::Value::try_from(Config::default()).unwrap();
+
+        let added_keys = add_missing_keys(&mut existing, defaults, "");
+
+        assert!(added_keys.contains(&String::from("build_config.content_listing_page")));
+        assert!(added_keys.contains(&String::from("development_config")));
+        assert!(!added_keys.contains(&String::from("content_dir")));
+
+        let config: Config = existing.try_into().unwrap();
+        assert_eq!("./posts", config.content_dir);
+        assert_eq!("./public", config.build_config.build_directory);
+        assert_eq!(9999, config.development_config.port);
+    }
+
+    #[test]
+    fn it_keeps_profiles_and_unknown_keys_when_merging_a_configuration_file() {
+        let project = tempfile::tempdir().unwrap();
+        let config_file = project.path().join("config.toml");
+        std::fs::write(
+            &config_file,
+            r#"
+content_dir = "./posts"
+custom_key = "kept"
+
+[profile.production.site]
+base_url = "https://example.com/"
+"#,
+        )
+        .unwrap();
+
+        let config = Config::init(config_file.clone(), InitMode::Merge, Config::default());
+        assert_eq!(9999, config.development_config.port);
+
+        let merged: toml::Value =
+            toml::from_str(&std::fs::read_to_string(&config_file).unwrap()).unwrap();
+        assert_eq!("kept", merged["custom_key"].as_str().unwrap());
+        assert_eq!(
+            "https://example.com/",
+            merged["profile"]["production"]["site"]["base_url"]
+                .as_str()
+                .unwrap()
+        );
+        assert_eq!("./posts", merged["content_dir"].as_str().unwrap());
+        assert_eq!(
+            "blog",
+            merged["build_config"]["content_listing_page"]
+                .as_str()
+                .unwrap()
+        );
+    }
+}
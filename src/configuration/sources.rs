@@ -0,0 +1,390 @@
+//! Layering of configuration sources. Values are resolved from the defaults, the configuration
+//! file, the selected `[profile.<name>]` table and `JHT_*` environment variables, each one
+//! overriding the previous.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::{find_unknown_keys, inline_schema, schema_at, Config};
+
+/// Prefix of the environment variables overriding configuration keys. Nested keys are
+/// separated by a double underscore, e.g. `JHT_DEVELOPMENT_CONFIG__PORT`.
+pub const ENVIRONMENT_PREFIX: &str = "JHT_";
+
+/// Environment variable selecting a profile when `--profile` is not given.
+pub const PROFILE_ENVIRONMENT_VARIABLE: &str = "JHT_PROFILE";
+
+const PROFILES_KEY: &str = "profile";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigSource {
+    Default,
+    File(PathBuf),
+    Profile(String),
+    Environment(String),
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(path) => write!(f, "{}", path.display()),
+            ConfigSource::Profile(name) => write!(f, "profile {}", name),
+            ConfigSource::Environment(variable) => write!(f, "environment variable {}", variable),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ResolvedConfig {
+    pub config: Config,
+    /// Source of every resolved value, keyed by its dotted path
+    pub sources: BTreeMap<String, ConfigSource>,
+    values: toml::Value,
+}
+
+impl ResolvedConfig {
+    pub fn new(
+        contents: &str,
+        file_path: &Path,
+        profile: Option<&str>,
+        environment: impl Iterator<Item = (String, String)>,
+    ) -> Result<Self, toml::de::Error> {
+        let mut file_values: toml::Value = toml::from_str(contents)?;
+        let mut profiles = take_profiles(&mut file_values);
+
+        warn_unknown_keys(&file_values, "");
+        for (name, overlay) in &profiles {
+            warn_unknown_keys(overlay, &format!("{}.{}", PROFILES_KEY, name));
+        }
+
+        let mut values =
+            toml::Value::try_from(Config::default()).expect("Failed to serialize default config");
+        let mut sources = BTreeMap::new();
+        record_sources(&values, "", &ConfigSource::Default, &mut sources);
+
+        merge(
+            &mut values,
+            file_values,
+            "",
+            &ConfigSource::File(file_path.to_path_buf()),
+            &mut sources,
+        );
+
+        if let Some(name) = profile {
+            let overlay = profiles.remove(name).unwrap_or_else(|| {
+                panic!(
+                    "Could not find profile {:?} in the configuration, available profiles: {:?}",
+                    name,
+                    profiles.keys().collect::<Vec<_>>()
+                )
+            });
+
+            log::debug!("Applying profile {:?}", name);
+            merge(
+                &mut values,
+                overlay,
+                "",
+                &ConfigSource::Profile(String::from(name)),
+                &mut sources,
+            );
+        }
+
+        // Only built when there is an environment variable to apply
+        let mut schema = None;
+        for (variable, value) in environment {
+            if !variable.starts_with(ENVIRONMENT_PREFIX) || variable == PROFILE_ENVIRONMENT_VARIABLE
+            {
+                continue;
+            }
+
+            let schema = schema.get_or_insert_with(inline_schema);
+            if let Some(overlay) = environment_overlay(&variable, &value, schema) {
+                log::debug!("Applying environment variable {}", variable);
+                merge(
+                    &mut values,
+                    overlay,
+                    "",
+                    &ConfigSource::Environment(variable),
+                    &mut sources,
+                );
+            }
+        }
+
+        let mut config: Config = values.clone().try_into()?;
+        config.set_keys = sources
+            .iter()
+            .filter(|(_, source)| **source != ConfigSource::Default)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        Ok(ResolvedConfig {
+            config,
+            sources,
+            values,
+        })
+    }
+
+    /// Every resolved value as a dotted path, its value and the source it came from.
+    pub fn entries(&self) -> Vec<(String, &toml::Value, ConfigSource)> {
+        let mut entries = Vec::new();
+        self.collect_entries(&self.values, String::new(), &mut entries);
+
+        entries
+    }
+
+    fn collect_entries<'a>(
+        &self,
+        value: &'a toml::Value,
+        path: String,
+        entries: &mut Vec<(String, &'a toml::Value, ConfigSource)>,
+    ) {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, value) in table {
+                    self.collect_entries(value, join_path(&path, key), entries);
+                }
+            }
+            _ => {
+                let source = self
+                    .sources
+                    .get(&path)
+                    .cloned()
+                    .unwrap_or(ConfigSource::Default);
+
+                entries.push((path, value, source));
+            }
+        }
+    }
+}
+
+fn take_profiles(values: &mut toml::Value) -> toml::value::Table {
+    let profiles = values
+        .as_table_mut()
+        .and_then(|table| table.remove(PROFILES_KEY));
+
+    match profiles {
+        None => toml::value::Table::new(),
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => panic!("The {:?} configuration key must be a table", PROFILES_KEY),
+    }
+}
+
+fn warn_unknown_keys(values: &toml::Value, prefix: &str) {
+    for mut unknown_key in find_unknown_keys(values) {
+        if !prefix.is_empty() {
+            unknown_key.path = join_path(prefix, &unknown_key.path);
+        }
+
+        log::warn!("{}", unknown_key);
+    }
+}
+
+/// Turns a `JHT_*` environment variable into a configuration overlay, with its value typed
+/// according to the configuration `schema`.
+fn environment_overlay(
+    variable: &str,
+    value: &str,
+    schema: &serde_json::Value,
+) -> Option<toml::Value> {
+    if variable == PROFILE_ENVIRONMENT_VARIABLE {
+        return None;
+    }
+
+    let key = variable.strip_prefix(ENVIRONMENT_PREFIX)?.to_lowercase();
+    let path: Vec<&str> = key.split("__").collect();
+
+    let value = match schema_at(schema, &path) {
+        Some(value_schema) => parse_environment_value(value, value_schema),
+        None => {
+            log::warn!(
+                "Environment variable {} does not match any configuration key",
+                variable
+            );
+            return None;
+        }
+    };
+
+    let overlay = path.iter().rev().fold(value, |value, key| {
+        let mut table = toml::value::Table::new();
+        table.insert(String::from(*key), value);
+        toml::Value::Table(table)
+    });
+
+    Some(overlay)
+}
+
+fn parse_environment_value(value: &str, schema: &serde_json::Value) -> toml::Value {
+    if accepts_type(schema, "string") {
+        return toml::Value::String(String::from(value));
+    }
+
+    toml::from_str::<toml::value::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(String::from(value)))
+}
+
+fn accepts_type(schema: &serde_json::Value, expected_type: &str) -> bool {
+    let matches_type = match schema.get("type") {
+        Some(serde_json::Value::String(t)) => t == expected_type,
+        Some(serde_json::Value::Array(types)) => types.iter().any(|t| t == expected_type),
+        _ => false,
+    };
+
+    matches_type
+        || ["anyOf", "oneOf", "allOf"]
+            .iter()
+            .filter_map(|combinator| schema.get(combinator).and_then(|s| s.as_array()))
+            .flatten()
+            .any(|subschema| accepts_type(subschema, expected_type))
+}
+
+/// Merges `overlay` into `base`. Tables are merged key by key, any other value replaces the
+/// one in `base`.
+fn merge(
+    base: &mut toml::Value,
+    overlay: toml::Value,
+    path: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match overlay {
+        toml::Value::Table(overlay_table) if base.is_table() => {
+            let base_table = base.as_table_mut().expect("Checked that base is a table");
+
+            for (key, value) in overlay_table {
+                let key_path = join_path(path, &key);
+
+                match base_table.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value, &key_path, source, sources),
+                    None => {
+                        record_sources(&value, &key_path, source, sources);
+                        base_table.insert(key, value);
+                    }
+                }
+            }
+        }
+        overlay => {
+            let nested_prefix = format!("{}.", path);
+            sources.retain(|key, _| key != path && !key.starts_with(&nested_prefix));
+
+            record_sources(&overlay, path, source, sources);
+            *base = overlay;
+        }
+    }
+}
+
+fn record_sources(
+    value: &toml::Value,
+    path: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                record_sources(value, &join_path(path, key), source, sources);
+            }
+        }
+        _ => {
+            sources.insert(String::from(path), source.clone());
+        }
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    match path {
+        "" => String::from(key),
+        _ => format!("{}.{}", path, key),
+    }
+}
+
+#[cfg(test)]
+mod test_sources {
+    use super::{ConfigSource, ResolvedConfig};
+    use std::path::{Path, PathBuf};
+
+    const CONFIG: &str = r#"
+[build_config]
+build_directory = "./build"
+
+[profile.production.build_config]
+build_directory = "./public"
+
+[profile.staging.build_config]
+content_listing_page = "posts"
+"#;
+
+    fn environment(variables: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        variables
+            .iter()
+            .map(|(k, v)| (String::from(*k), String::from(*v)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn it_applies_the_selected_profile_on_top_of_the_file() {
+        let resolved = ResolvedConfig::new(
+            CONFIG,
+            Path::new("config.toml"),
+            Some("production"),
+            environment(&[]),
+        )
+        .unwrap();
+
+        assert_eq!("./public", resolved.config.build_config.build_directory);
+        assert!(resolved
+            .config
+            .set_keys
+            .contains("build_config.build_directory"));
+        assert!(!resolved.config.set_keys.contains("site.title"));
+        assert_eq!("blog", resolved.config.build_config.content_listing_page);
+        assert_eq!(
+            Some(&ConfigSource::Profile(String::from("production"))),
+            resolved.sources.get("build_config.build_directory")
+        );
+    }
+
+    #[test]
+    fn it_ignores_profiles_that_are_not_selected() {
+        let resolved =
+            ResolvedConfig::new(CONFIG, Path::new("config.toml"), None, environment(&[])).unwrap();
+
+        assert_eq!("./build", resolved.config.build_config.build_directory);
+        assert_eq!(
+            Some(&ConfigSource::File(PathBuf::from("config.toml"))),
+            resolved.sources.get("build_config.build_directory")
+        );
+        assert_eq!(
+            Some(&ConfigSource::Default),
+            resolved.sources.get("development_config.port")
+        );
+    }
+
+    #[test]
+    fn it_lets_environment_variables_override_profiles() {
+        let resolved = ResolvedConfig::new(
+            CONFIG,
+            Path::new("config.toml"),
+            Some("staging"),
+            environment(&[
+                ("JHT_DEVELOPMENT_CONFIG__PORT", "8080"),
+                ("JHT_BUILD_CONFIG__BUILD_DIRECTORY", "1234"),
+                ("JHT_PROFILE", "staging"),
+                ("HOME", "/root"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(8080, resolved.config.development_config.port);
+        assert_eq!("1234", resolved.config.build_config.build_directory);
+        assert_eq!("posts", resolved.config.build_config.content_listing_page);
+        assert_eq!(
+            Some(&ConfigSource::Environment(String::from(
+                "JHT_DEVELOPMENT_CONFIG__PORT"
+            ))),
+            resolved.sources.get("development_config.port")
+        );
+    }
+}
@@ -1,47 +1,241 @@
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use super::configuration;
 use crate::ConfigurationFilePath;
 use clap::Parser;
+#[allow(clippy::single_component_path_imports)]
 use log;
 
+mod prompt;
+
 #[derive(Parser)]
 #[clap(version, about)]
 pub struct InitCommand {
     #[clap(short, long, global = true)]
     debug: bool,
 
-    #[clap(short = 'f', long = "file", global = true)]
+    #[clap(short = 'f', long = "file", global = true, value_parser = crate::parse_configuration_file_path)]
     configuration_file_path: Option<String>,
+
+    /// Overwrite an existing configuration with the defaults, keeping a backup of it
+    #[clap(long, conflicts_with = "merge")]
+    force: bool,
+
+    /// Add missing keys to an existing configuration, keeping a backup of it
+    #[clap(long)]
+    merge: bool,
+
+    /// Ask for every setting not given as a flag instead of using the defaults
+    #[clap(short, long)]
+    interactive: bool,
+
+    /// Title of the website
+    #[clap(long)]
+    title: Option<String>,
+
+    /// URL the website is served from, e.g. https://example.com/
+    #[clap(long)]
+    base_url: Option<String>,
+
+    /// Name of the listing page, without the .html extension
+    #[clap(long)]
+    listing_page: Option<String>,
+
+    /// Directory containing the Markdown content files
+    #[clap(long)]
+    content_dir: Option<String>,
+
+    /// Directory containing the templates
+    #[clap(long)]
+    templates_dir: Option<String>,
+
+    /// Directory the website is built into
+    #[clap(long)]
+    build_dir: Option<String>,
+
+    /// Only create the configuration and empty content and templates directories, without the
+    /// starter site
+    #[clap(long)]
+    bare: bool,
+
+    /// Initialise a git repository in the project directory
+    #[clap(long)]
+    git: bool,
 }
 
+/// Starter site files, `{listing_page}` and `{site_title}` are replaced with the configured
+/// values when they are written.
+const STARTER_INDEX: &str = include_str!("starter/index.html");
+const STARTER_LISTING: &str = include_str!("starter/listing.html");
+const STARTER_CONTENT: &str = include_str!("starter/content.html");
+const STARTER_STYLESHEET: &str = include_str!("starter/style.css");
+const STARTER_POST: &str = include_str!("starter/hello-world.md");
+
 impl super::ConfigurationFilePath for InitCommand {
     fn configuration_file_path(&self) -> &Option<String> {
         &self.configuration_file_path
     }
 }
 
+/// Settings of the new project, taken from the flags or asked for in interactive mode.
+struct Answers {
+    title: String,
+    base_url: String,
+    listing_page: String,
+    content_dir: String,
+    templates_dir: String,
+    build_dir: String,
+    starter_site: bool,
+    git_repository: bool,
+}
+
+impl Answers {
+    fn collect(init_command: &InitCommand) -> Self {
+        let defaults = configuration::Config::default();
+        let interactive = init_command.interactive;
+
+        let answer = |flag: &Option<String>, question: &str, default: &str| match flag {
+            Some(value) => value.clone(),
+            None if interactive => prompt::ask(question, default),
+            None => String::from(default),
+        };
+
+        Answers {
+            title: answer(&init_command.title, "Site title", &defaults.site.title),
+            base_url: answer(&init_command.base_url, "Base URL", &defaults.site.base_url),
+            listing_page: answer(
+                &init_command.listing_page,
+                "Listing page name",
+                &defaults.build_config.content_listing_page,
+            ),
+            content_dir: answer(
+                &init_command.content_dir,
+                "Content directory",
+                &defaults.content_dir,
+            ),
+            templates_dir: answer(
+                &init_command.templates_dir,
+                "Templates directory",
+                &defaults.templates_directory,
+            ),
+            build_dir: answer(
+                &init_command.build_dir,
+                "Build directory",
+                &defaults.build_config.build_directory,
+            ),
+            starter_site: match (init_command.bare, interactive) {
+                (true, _) => false,
+                (false, true) => prompt::confirm("Create starter templates?", true),
+                (false, false) => true,
+            },
+            git_repository: match (init_command.git, interactive) {
+                (true, _) => true,
+                (false, true) => prompt::confirm("Create a git repository?", true),
+                (false, false) => false,
+            },
+        }
+    }
+
+    fn config(&self) -> configuration::Config {
+        let mut config = configuration::Config::default();
+
+        config.site.title = self.title.clone();
+        config.site.base_url = self.base_url.clone();
+        config.content_dir = self.content_dir.clone();
+        config.templates_directory = self.templates_dir.clone();
+        config.content_template =
+            format!("{}/content.html", self.templates_dir.trim_end_matches('/'));
+        config.build_config.build_directory = self.build_dir.clone();
+        config.build_config.content_directory = format!(
+            "{}/{}",
+            self.build_dir.trim_end_matches('/'),
+            self.listing_page
+        );
+        config.build_config.content_listing_page = self.listing_page.clone();
+
+        config
+    }
+}
+
 pub fn command(init_command: &InitCommand) {
     log::info!("Running init command");
 
-    // TODO: More things that this command could do
-    // - Create git repository
-    // - Create simple templates for
-    //      - index.html
-    //      - content.html
-    //      - blog.html
-    let configuration_file_path = init_command.path();
+    let configuration_file_path = init_command.local_path();
 
     log::info!(
         "Initializing configuration in {:?}",
         configuration_file_path
     );
 
-    let configuration = configuration::Config::init(configuration_file_path);
+    let mode = match (init_command.force, init_command.merge) {
+        (true, _) => configuration::InitMode::Force,
+        (_, true) => configuration::InitMode::Merge,
+        _ => configuration::InitMode::Create,
+    };
+
+    // Before any question, so the answers are not lost to an existing configuration
+    configuration::Config::check_init(&configuration_file_path, mode);
+    let answers = Answers::collect(init_command);
+
+    let project_directory = match configuration_file_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let configuration =
+        configuration::Config::init(configuration_file_path, mode, answers.config());
     create_content_directory(&configuration);
     creaet_templates_directory(&configuration);
 
-    create_gitignore();
+    if answers.starter_site {
+        create_starter_site(&configuration);
+    }
+
+    create_gitignore(&configuration, &project_directory);
+
+    if answers.git_repository {
+        create_git_repository(&project_directory);
+    }
+}
+
+fn create_starter_site(config: &configuration::Config) {
+    log::info!("Creating starter site");
+
+    let templates_directory = Path::new(&config.templates_directory);
+    let listing_page = &config.build_config.content_listing_page;
+
+    let mut post_path = PathBuf::from(&config.content_dir);
+    post_path.push("hello-world.md");
+
+    let starter_files = [
+        (templates_directory.join("index.html"), STARTER_INDEX),
+        (
+            templates_directory.join(format!("{}.html", listing_page)),
+            STARTER_LISTING,
+        ),
+        (PathBuf::from(&config.content_template), STARTER_CONTENT),
+        (templates_directory.join("style.css"), STARTER_STYLESHEET),
+        (post_path, STARTER_POST),
+    ];
+
+    for (path, contents) in starter_files {
+        if path.exists() {
+            log::info!("Found {:?}, skipping creation", path);
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Failed to create starter site directory");
+        }
+
+        log::debug!("Creating {:?}", path);
+        let contents = contents
+            .replace("{listing_page}", listing_page)
+            .replace("{site_title}", &config.site.title);
+
+        std::fs::write(&path, contents).expect("Failed to write starter site file");
+    }
 }
 
 fn create_content_directory(config: &configuration::Config) {
@@ -54,17 +248,22 @@ fn creaet_templates_directory(config: &configuration::Config) {
         .expect("Failed to create build directory");
 }
 
-fn create_gitignore() {
-    let mut gitignore_path = std::path::PathBuf::new();
-    gitignore_path.push(".");
-    gitignore_path.push(".gitgnore");
+fn create_gitignore(config: &configuration::Config, project_directory: &Path) {
+    let gitignore_path = project_directory.join(".gitignore");
 
     if gitignore_path.exists() {
         log::debug!("Found .gitignore file, skipping creation.");
         return;
     }
 
-    let gitignore_contents = String::from("just-html");
+    let build_directory = Path::new(&config.build_config.build_directory);
+    let ignored_build_directory = build_directory
+        .strip_prefix(project_directory)
+        .unwrap_or(build_directory)
+        .to_string_lossy()
+        .replace('\\', "/");
+
+    let gitignore_contents = format!("/{}/\n", ignored_build_directory);
 
     let mut gitignore_file =
         std::fs::File::create(&gitignore_path).expect("failed to create a .gitignore file");
@@ -72,3 +271,24 @@ fn create_gitignore() {
         .write_all(gitignore_contents.as_bytes())
         .expect("failed to write to .gitignore file")
 }
+
+fn create_git_repository(project_directory: &Path) {
+    if project_directory.join(".git").exists() {
+        log::info!("Found a git repository, skipping creation.");
+        return;
+    }
+
+    log::info!("Creating git repository in {:?}", project_directory);
+
+    let status = std::process::Command::new("git")
+        .arg("init")
+        .arg("--quiet")
+        .current_dir(project_directory)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => log::warn!("git init failed with {}", status),
+        Err(e) => log::warn!("Could not run git to create a repository: {}", e),
+    }
+}
@@ -0,0 +1,70 @@
+use std::io::{BufRead, Write};
+
+/// Asks a question on the terminal, returning `default` when the answer is empty.
+pub fn ask(question: &str, default: &str) -> String {
+    print!("{} [{}]: ", question, default);
+    std::io::stdout()
+        .flush()
+        .expect("Failed to write to the terminal");
+
+    let answer = read_answer();
+
+    match answer.is_empty() {
+        true => String::from(default),
+        false => answer,
+    }
+}
+
+/// Asks a yes or no question on the terminal until it gets a valid answer.
+pub fn confirm(question: &str, default: bool) -> bool {
+    let choices = match default {
+        true => "Y/n",
+        false => "y/N",
+    };
+
+    loop {
+        print!("{} [{}]: ", question, choices);
+        std::io::stdout()
+            .flush()
+            .expect("Failed to write to the terminal");
+
+        match parse_confirmation(&read_answer(), default) {
+            Some(confirmed) => return confirmed,
+            None => println!("Please answer yes or no."),
+        }
+    }
+}
+
+fn read_answer() -> String {
+    let mut answer = String::new();
+
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .expect("Failed to read from the terminal");
+
+    String::from(answer.trim())
+}
+
+fn parse_confirmation(answer: &str, default: bool) -> Option<bool> {
+    match answer.to_lowercase().as_str() {
+        "" => Some(default),
+        "y" | "yes" => Some(true),
+        "n" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test_prompt {
+    use super::parse_confirmation;
+
+    #[test]
+    fn it_parses_confirmations_falling_back_to_the_default() {
+        assert_eq!(Some(true), parse_confirmation("", true));
+        assert_eq!(Some(false), parse_confirmation("", false));
+        assert_eq!(Some(true), parse_confirmation("Yes", false));
+        assert_eq!(Some(false), parse_confirmation("n", true));
+        assert_eq!(None, parse_confirmation("maybe", true));
+    }
+}
@@ -1,14 +1,13 @@
 use clap::{Parser, Subcommand};
+#[allow(clippy::single_component_path_imports)]
 use plogger;
-/// ---
-/// Next steps:
-/// - Init Script
-///     - Create basic empty HTMLs (index.html, blog.html, content.html)
-/// ----
 mod build;
+mod check;
+mod clean;
 mod config;
 mod configuration;
 mod init;
+mod new;
 mod serve;
 mod utils;
 
@@ -23,8 +22,14 @@ struct Cli {
     #[clap(short, long, global = true)]
     debug: bool,
 
-    #[clap(short = 'f', long = "file", global = true)]
+    /// Configuration file to use, by default the closest config.toml or jht.toml found in the
+    /// current directory or its parents
+    #[clap(short = 'f', long = "file", global = true, value_parser = parse_configuration_file_path)]
     configuration_file_path: Option<String>,
+
+    /// Configuration profile to apply, e.g. `production` for `[profile.production]`
+    #[clap(long, global = true)]
+    profile: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -37,8 +42,15 @@ enum Commands {
     Config(config::ConfigCommand),
     /// Serve your website from the build directory
     Serve(serve::ServeCommand),
+    /// Create a content file from an archetype
+    New(new::NewCommand),
+    /// Remove the files built by just-html from the build directory
+    Clean(clean::CleanCommand),
+    /// Check the built website for problems
+    Check(check::CheckCommand),
 }
 
+#[allow(clippy::needless_return)]
 fn main() {
     let cli = Cli::parse();
 
@@ -52,16 +64,43 @@ fn main() {
             init::command(&command);
         }
         Some(Commands::Build(command)) => {
-            let config = configuration::Config::load(configuration_file_path.to_path_buf());
+            let config = configuration::Config::load(
+                configuration_file_path.to_path_buf(),
+                cli.profile.as_deref(),
+            );
             build::command(&command, &config);
         }
         Some(Commands::Config(command)) => {
             config::command(&command);
         }
         Some(Commands::Serve(command)) => {
-            let config = configuration::Config::load(configuration_file_path.to_path_buf());
+            let config = configuration::Config::load(
+                configuration_file_path.to_path_buf(),
+                cli.profile.as_deref(),
+            );
             serve::command(&command, &config);
         }
+        Some(Commands::New(command)) => {
+            let config = configuration::Config::load(
+                configuration_file_path.to_path_buf(),
+                cli.profile.as_deref(),
+            );
+            new::command(&command, &config);
+        }
+        Some(Commands::Clean(command)) => {
+            let config = configuration::Config::load(
+                configuration_file_path.to_path_buf(),
+                cli.profile.as_deref(),
+            );
+            clean::command(&command, &config);
+        }
+        Some(Commands::Check(command)) => {
+            let config = configuration::Config::load(
+                configuration_file_path.to_path_buf(),
+                cli.profile.as_deref(),
+            );
+            check::command(&command, &config);
+        }
         None => {
             failure_message(); // Note that this will be handled by clap
         }
@@ -77,17 +116,39 @@ fn failure_message() {
 pub trait ConfigurationFilePath {
     fn configuration_file_path(&self) -> &Option<String>;
 
+    /// Path of the configuration file given with `-f`, or the closest configuration file found
+    /// in the current directory or its parents.
+    #[allow(clippy::needless_arbitrary_self_type)]
     fn path(self: &Self) -> std::path::PathBuf {
         match &self.configuration_file_path() {
-            Some(path) => {
-                std::path::Path::new(&path).to_owned()
-                // TODO: Validate that it always ends in .toml
+            Some(path) => std::path::Path::new(&path).to_owned(),
+            None => {
+                let current_directory =
+                    std::env::current_dir().expect("Could not read the current directory");
+
+                configuration::discover(&current_directory)
+                    .unwrap_or_else(|| current_directory.join("config.toml"))
             }
+        }
+    }
+
+    /// Path of the configuration file given with `-f`, or `config.toml` in the current
+    /// directory. Used when creating a new configuration rather than loading one.
+    fn local_path(&self) -> std::path::PathBuf {
+        match &self.configuration_file_path() {
+            Some(path) => std::path::Path::new(&path).to_owned(),
             None => std::path::Path::new("./config.toml").to_owned(),
         }
     }
 }
 
+fn parse_configuration_file_path(path: &str) -> Result<String, String> {
+    match std::path::Path::new(path).extension() {
+        Some(extension) if extension == "toml" => Ok(String::from(path)),
+        _ => Err(String::from("the configuration file must be a .toml file")),
+    }
+}
+
 impl ConfigurationFilePath for Cli {
     fn configuration_file_path(&self) -> &Option<String> {
         &self.configuration_file_path
@@ -0,0 +1,158 @@
+use std::path::{Path, PathBuf};
+
+use crate::configuration::Config;
+use crate::utils;
+use clap::Parser;
+
+/// Archetype used when the archetypes directory has neither `<kind>.md` nor `default.md`.
+const DEFAULT_ARCHETYPE: &str = include_str!("default.md");
+
+#[derive(Parser)]
+pub struct NewCommand {
+    #[clap(short, long, global = true)]
+    debug: bool,
+
+    /// Kind of content to create, selects the `archetypes/<kind>.md` archetype
+    kind: String,
+
+    /// Title of the new content, also used to name its file
+    title: String,
+
+    /// Author of the new content, defaults to `site.author`
+    #[clap(long)]
+    author: Option<String>,
+
+    /// Create the content as published instead of as a draft
+    #[clap(long)]
+    published: bool,
+}
+
+pub fn command(command: &NewCommand, config: &Config) {
+    log::info!("Creating new {} {:?}", command.kind, command.title);
+
+    if let Err(message) = validate_title(&command.title) {
+        log::error!("{}", message);
+        std::process::exit(1);
+    }
+
+    let slug = utils::slugify(&command.title);
+    if slug.is_empty() {
+        log::error!("Could not make a file name out of {:?}", command.title);
+        std::process::exit(1);
+    }
+
+    let content_path = PathBuf::from(&config.content_dir).join(format!("{}.md", slug));
+
+    if content_path.exists() {
+        log::error!(
+            "{:?} already exists, pick another title or remove it first",
+            content_path
+        );
+        std::process::exit(1);
+    }
+
+    let archetype = load_archetype(Path::new(&config.archetypes_directory), &command.kind);
+
+    let author = command
+        .author
+        .clone()
+        .unwrap_or_else(|| config.site.author.clone());
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let draft = (!command.published).to_string();
+
+    let contents = fill_archetype(
+        &archetype,
+        &[
+            ("title", &command.title),
+            ("slug", &slug),
+            ("date", &date),
+            ("draft", &draft),
+            ("author", &author),
+        ],
+    );
+
+    std::fs::create_dir_all(&config.content_dir).expect("Failed to create content directory");
+    std::fs::write(&content_path, contents).expect("Failed to write new content file");
+
+    log::info!("Created {:?}", content_path);
+}
+
+/// Loads `<kind>.md` from the archetypes directory, falling back to `default.md` and then to
+/// the built-in archetype.
+fn load_archetype(archetypes_directory: &Path, kind: &str) -> String {
+    let candidates = [
+        archetypes_directory.join(format!("{}.md", kind)),
+        archetypes_directory.join("default.md"),
+    ];
+
+    match candidates.iter().find(|candidate| candidate.is_file()) {
+        Some(path) => {
+            log::debug!("Using archetype {:?}", path);
+            std::fs::read_to_string(path).expect("Could not read archetype")
+        }
+        None => {
+            log::debug!("No archetype found for {:?}, using the default one", kind);
+            String::from(DEFAULT_ARCHETYPE)
+        }
+    }
+}
+
+/// Frontmatter lines are split at their last `": "`, so a title containing one would be read
+/// back as part of the key.
+fn validate_title(title: &str) -> Result<(), String> {
+    match title.contains(": ") {
+        true => Err(format!(
+            "Titles cannot contain \": \", which frontmatter cannot hold: {:?}",
+            title
+        )),
+        false => Ok(()),
+    }
+}
+
+/// Replaces every `{name}` of the archetype with its value, leaving out the lines of the
+/// placeholders without a value, e.g. `author: {author}` when there is no author.
+fn fill_archetype(archetype: &str, values: &[(&str, &str)]) -> String {
+    archetype
+        .split_inclusive('\n')
+        .filter(|line| {
+            !values
+                .iter()
+                .any(|(name, value)| value.is_empty() && line.contains(&format!("{{{}}}", name)))
+        })
+        .map(|line| {
+            values
+                .iter()
+                .fold(String::from(line), |line, (name, value)| {
+                    line.replace(&format!("{{{}}}", name), value)
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_new {
+    use super::{fill_archetype, validate_title, DEFAULT_ARCHETYPE};
+
+    #[test]
+    fn it_rejects_titles_the_frontmatter_cannot_hold() {
+        assert!(validate_title("Rust: Part 1").is_err());
+        assert!(validate_title("Rust:Part 1").is_ok());
+        assert!(validate_title("Ratio 16:9").is_ok());
+    }
+
+    #[test]
+    fn it_leaves_out_the_lines_of_empty_values() {
+        assert_eq!(
+            "---\ntitle: Hello\ndate: 2022-08-01\ndraft: true\n---\n\n",
+            fill_archetype(
+                DEFAULT_ARCHETYPE,
+                &[
+                    ("title", "Hello"),
+                    ("date", "2022-08-01"),
+                    ("draft", "true"),
+                    ("author", ""),
+                ],
+            )
+        );
+    }
+}
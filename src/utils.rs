@@ -1,3 +1,7 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+#[allow(clippy::match_like_matches_macro)]
 pub fn is_stylesheet(path: &std::path::Path) -> bool {
     match path.extension() {
         None => false,
@@ -8,6 +12,7 @@ pub fn is_stylesheet(path: &std::path::Path) -> bool {
     }
 }
 
+#[allow(clippy::match_like_matches_macro)]
 pub fn is_plaintext_file(path: &std::path::Path) -> bool {
     match path.extension() {
         None => false,
@@ -18,3 +23,84 @@ pub fn is_plaintext_file(path: &std::path::Path) -> bool {
         },
     }
 }
+
+/// Lists every file in `directory` and its subdirectories, or nothing if it does not exist.
+pub fn list_files(directory: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut files = Vec::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            files.extend(list_files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Turns a title into a lowercase, dash separated name usable in file names and URLs.
+pub fn slugify(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Joins the components of a relative path with `/`, whatever the platform.
+pub fn to_url_path(path: &std::path::Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Joins a path relative to the root of the site onto the site's base URL.
+pub fn join_url(base_url: &str, path: &str) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), path)
+}
+
+/// Whether a link points to another site or uses a scheme of its own, e.g. `mailto:`.
+pub fn is_external_url(href: &str) -> bool {
+    lazy_static! {
+        static ref SCHEME_REGEX: Regex = Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*:").unwrap();
+    }
+
+    href.starts_with("//") || SCHEME_REGEX.is_match(href)
+}
+
+#[cfg(test)]
+mod test_utils {
+    use super::{join_url, slugify};
+
+    #[test]
+    fn it_slugifies_titles() {
+        assert_eq!("my-title", slugify("My Title"));
+        assert_eq!("hello-world", slugify("  Hello, World!  "));
+        assert_eq!("rust-2021-edition", slugify("Rust -- 2021 Edition"));
+        assert_eq!("café-crème", slugify("Café Crème"));
+    }
+
+    #[test]
+    fn it_joins_urls_onto_the_base_url() {
+        assert_eq!("/blog/post.html", join_url("/", "blog/post.html"));
+        assert_eq!(
+            "https://example.com/blog/post.html",
+            join_url("https://example.com/", "blog/post.html")
+        );
+        assert_eq!(
+            "https://example.com/docs/index.html",
+            join_url("https://example.com/docs", "index.html")
+        );
+    }
+}